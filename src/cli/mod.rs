@@ -1,8 +1,14 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 
+use crate::config::WallpaperOptions;
 use crate::ipc::client::IpcClient;
 use crate::ipc::protocol::IpcResponse;
+use crate::wallpaper::audio::AudioOutput;
+use crate::wallpaper::devices::DeviceSelector;
+use crate::wallpaper::effects::Effects;
+use crate::wallpaper::hwaccel::{HardwareAcceleration, probe_devices};
+use crate::wayland::{Anchor, Color, ColorQuality, Layer, Margins, ScalingFilter, ScalingMode};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -16,18 +22,130 @@ pub struct Args {
 
 #[derive(Subcommand, Debug)]
 pub enum Command {
-    Set { path: String },
+    Set {
+        path: String,
+        /// 输出名称（如 DP-1），不指定时应用到所有输出
+        #[arg(short, long)]
+        output: Option<String>,
+        /// 缩放方式：fill（裁剪铺满）、fit（完整显示）、stretch、center、tile、span（横跨所有输出）
+        #[arg(long)]
+        scaling: Option<ScalingMode>,
+        /// fit/center/tile 模式下的背景色，例如 "#000000"
+        #[arg(long)]
+        background: Option<Color>,
+        /// 画质：low（RGB565，省内存）、normal（XRGB8888）、high（10 位色深）
+        #[arg(long)]
+        quality: Option<ColorQuality>,
+        /// 缩放算法：fast-bilinear（默认）、bilinear、bicubic、lanczos
+        #[arg(long)]
+        scaling_filter: Option<ScalingFilter>,
+        /// layer-shell 层：background（默认）、bottom、top、overlay
+        #[arg(long)]
+        layer: Option<Layer>,
+        /// layer surface 的 namespace，用于 compositor 规则匹配
+        #[arg(long)]
+        namespace: Option<String>,
+        /// 锚定的边，例如 "all" 或 "top,left"
+        #[arg(long)]
+        anchor: Option<Anchor>,
+        /// 边距（逻辑像素），例如 "10" 或 "0,20,0,20"（上、右、下、左）
+        #[arg(long, allow_hyphen_values = true)]
+        margins: Option<Margins>,
+        /// 不透明度 0.0 - 1.0
+        #[arg(long)]
+        opacity: Option<f64>,
+        /// span 模式下屏幕四周的边框宽度（毫米）
+        #[arg(long)]
+        bezel: Option<f64>,
+        /// 音频输出：off（默认）、auto、pipewire、pulseaudio、alsa、null、wav:<path>
+        #[arg(long)]
+        audio: Option<AudioOutput>,
+        /// 硬件解码：auto（默认）、vaapi、cuda、vdpau、qsv、vulkan、software
+        #[arg(long)]
+        hwaccel: Option<HardwareAcceleration>,
+        /// 解码设备：render node 路径、PCI ID（如 10de:25a0）或驱动名（如 amdgpu），见 devices 命令
+        #[arg(long)]
+        device: Option<DeviceSelector>,
+        #[command(flatten)]
+        effects: EffectArgs,
+    },
     Get,
     Status,
+    /// 设置音量（0 - 100）
+    Volume {
+        #[arg(value_parser = clap::value_parser!(u32).range(0..=100))]
+        percent: u32,
+    },
+    /// 静音
+    Mute,
+    /// 取消静音
+    Unmute,
+    /// 修改正在播放的壁纸的画面效果，不指定任何效果时清除效果
+    Effects {
+        /// 输出名称（如 DP-1），不指定时应用到所有壁纸
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        effects: EffectArgs,
+    },
+    /// 列出可用的解码设备和它们支持的格式
+    Devices,
     Shutdown,
 }
 
+/// 画面效果参数，set 和 effects 命令共用
+#[derive(clap::Args, Debug)]
+pub struct EffectArgs {
+    /// 亮度 -1.0 - 1.0，负值变暗
+    #[arg(long, allow_hyphen_values = true)]
+    brightness: Option<f64>,
+    /// 对比度 0.0 - 3.0，默认 1
+    #[arg(long)]
+    contrast: Option<f64>,
+    /// 饱和度 0.0 - 3.0，默认 1，0 为黑白
+    #[arg(long)]
+    saturation: Option<f64>,
+    /// 高斯模糊强度 0 - 100
+    #[arg(long)]
+    blur: Option<f64>,
+    /// 自定义 FFmpeg filtergraph，例如 "hue=h=90,hflip"
+    #[arg(long)]
+    filter: Option<String>,
+}
+
+impl EffectArgs {
+    /// 没有指定任何效果时返回 None
+    fn into_effects(self) -> Option<Effects> {
+        let effects = Effects {
+            brightness: self.brightness,
+            contrast: self.contrast,
+            saturation: self.saturation,
+            blur: self.blur,
+            filter: self.filter,
+        };
+        (effects != Effects::default()).then_some(effects)
+    }
+}
+
 pub fn execute_command(args: Args) -> Result<()> {
+    // 设备列表在本地探测，不需要守护进程
+    if let Command::Devices = args.command {
+        return list_devices();
+    }
+
     let mut client = IpcClient::connect(&args.socket)?;
 
     match args.command {
-        Command::Set { path } => {
-            let response = client.set_wallpaper(path)?;
+        Command::Set { path, output, scaling, background, quality, scaling_filter, layer, namespace, anchor, margins, opacity, bezel, audio, hwaccel, device, effects } => {
+            if let Some(opacity) = opacity.filter(|o| !(0.0..=1.0).contains(o)) {
+                return Err(anyhow::anyhow!("Opacity must be between 0.0 and 1.0, got {}", opacity));
+            }
+            let effects = effects.into_effects();
+            if let Some(Err(e)) = effects.as_ref().map(Effects::validate) {
+                return Err(anyhow::anyhow!("{}", e));
+            }
+            let options = WallpaperOptions { scaling, background, quality, scaling_filter, layer, namespace, anchor, margins, opacity, bezel, audio, hwaccel, device, effects };
+            let response = client.set_wallpaper(path, output, options)?;
             handle_response(response)?;
         }
         Command::Get => {
@@ -38,6 +156,25 @@ pub fn execute_command(args: Args) -> Result<()> {
             let response = client.get_status()?;
             handle_response(response)?;
         }
+        Command::Volume { percent } => {
+            let response = client.set_volume(percent as f32 / 100.0)?;
+            handle_response(response)?;
+        }
+        Command::Mute => {
+            let response = client.set_muted(true)?;
+            handle_response(response)?;
+        }
+        Command::Unmute => {
+            let response = client.set_muted(false)?;
+            handle_response(response)?;
+        }
+        Command::Effects { output, effects } => {
+            let effects = effects.into_effects().unwrap_or_default();
+            effects.validate().map_err(|e| anyhow::anyhow!("{}", e))?;
+            let response = client.set_effects(output, effects)?;
+            handle_response(response)?;
+        }
+        Command::Devices => unreachable!("handled before connecting"),
         Command::Shutdown => {
             let response = client.shutdown()?;
             handle_response(response)?;
@@ -58,8 +195,15 @@ fn handle_response(response: IpcResponse) -> Result<()> {
                 None => println!("No wallpaper set"),
             }
         }
-        crate::ipc::protocol::IpcResponse::Status { running } => {
+        crate::ipc::protocol::IpcResponse::Status { running, wallpapers } => {
             println!("Daemon status: {}", if running { "Running" } else { "Stopped" });
+            for wallpaper in wallpapers {
+                print!("  {}: {} (wayland: {}", wallpaper.output, wallpaper.path, wallpaper.connection);
+                if let Some(decoder) = &wallpaper.decoder {
+                    print!(", decoder: {}", decoder);
+                }
+                println!(")");
+            }
         }
         crate::ipc::protocol::IpcResponse::Error { message } => {
             eprintln!("Error: {}", message);
@@ -68,3 +212,23 @@ fn handle_response(response: IpcResponse) -> Result<()> {
     }
     Ok(())
 }
+
+/// 打印每个 render node 和其上可用的解码后端
+fn list_devices() -> Result<()> {
+    let devices = probe_devices()?;
+    if devices.is_empty() {
+        println!("No render nodes found in /dev/dri");
+        return Ok(());
+    }
+    for (node, backends) in devices {
+        println!("{}", node);
+        for (hw_accel_type, codecs) in backends {
+            match codecs {
+                Ok(codecs) if codecs.is_empty() => println!("  {}: no supported codecs", hw_accel_type),
+                Ok(codecs) => println!("  {}: {}", hw_accel_type, codecs.join(", ")),
+                Err(e) => println!("  {}: unavailable ({})", hw_accel_type, e),
+            }
+        }
+    }
+    Ok(())
+}
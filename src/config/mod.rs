@@ -0,0 +1,208 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::wallpaper::audio::AudioOutput;
+use crate::wallpaper::devices::DeviceSelector;
+use crate::wallpaper::effects::Effects;
+use crate::wallpaper::hwaccel::HardwareAcceleration;
+use crate::wayland::{Anchor, Color, ColorQuality, DEFAULT_NAMESPACE, Layer, Margins, ScalingFilter, ScalingMode, SurfaceOptions};
+
+/// 单个壁纸的可选设置，未设置的字段依次回退到输出配置、默认配置和内置默认值
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WallpaperOptions {
+    /// 缩放方式：fill / fit / stretch / center / tile
+    pub scaling: Option<ScalingMode>,
+    /// 背景色，例如 "#000000"
+    pub background: Option<Color>,
+    /// 画质：low（RGB565）/ normal（XRGB8888）/ high（10 位）
+    pub quality: Option<ColorQuality>,
+    /// 缩放算法：fast-bilinear / bilinear / bicubic / lanczos
+    pub scaling_filter: Option<ScalingFilter>,
+    /// layer-shell 层：background / bottom / top / overlay
+    pub layer: Option<Layer>,
+    /// layer surface 的 namespace，默认 "waypaper-rs"
+    pub namespace: Option<String>,
+    /// 锚定的边，例如 "all" 或 "top,left"
+    pub anchor: Option<Anchor>,
+    /// 边距，例如 "10" 或 "0,20,0,20"（上、右、下、左）
+    pub margins: Option<Margins>,
+    /// 不透明度 0.0 - 1.0
+    pub opacity: Option<f64>,
+    /// span 模式下屏幕四周的边框宽度（毫米）
+    pub bezel: Option<f64>,
+    /// 音频输出：off（默认）/ auto / pipewire / pulseaudio / alsa / null / wav:<path>
+    pub audio: Option<AudioOutput>,
+    /// 硬件解码：auto（默认）/ vaapi / cuda / vdpau / qsv / vulkan / software
+    pub hwaccel: Option<HardwareAcceleration>,
+    /// 解码设备：render node 路径、PCI ID（如 "10de:25a0"）或驱动名（如 "amdgpu"）
+    pub device: Option<DeviceSelector>,
+    /// 画面效果，例如 { "brightness": -0.2, "blur": 8 }，整体覆盖而不是逐项合并
+    pub effects: Option<Effects>,
+}
+
+impl WallpaperOptions {
+    /// 用 `fallback` 补全未设置的字段
+    pub fn or(self, fallback: &WallpaperOptions) -> Self {
+        Self {
+            scaling: self.scaling.or(fallback.scaling),
+            background: self.background.or(fallback.background),
+            quality: self.quality.or(fallback.quality),
+            scaling_filter: self.scaling_filter.or(fallback.scaling_filter),
+            layer: self.layer.or(fallback.layer),
+            namespace: self.namespace.or_else(|| fallback.namespace.clone()),
+            anchor: self.anchor.or(fallback.anchor),
+            margins: self.margins.or(fallback.margins),
+            opacity: self.opacity.or(fallback.opacity),
+            bezel: self.bezel.or(fallback.bezel),
+            audio: self.audio.or_else(|| fallback.audio.clone()),
+            hwaccel: self.hwaccel.or(fallback.hwaccel),
+            device: self.device.or_else(|| fallback.device.clone()),
+            effects: self.effects.or_else(|| fallback.effects.clone()),
+        }
+    }
+
+    /// 转换为 Wayland surface 的显示选项
+    pub fn surface_options(&self) -> SurfaceOptions {
+        SurfaceOptions {
+            scaling: self.scaling.unwrap_or_default(),
+            background: self.background.unwrap_or_default(),
+            quality: self.quality.unwrap_or_default(),
+            scaling_filter: self.scaling_filter.unwrap_or_default(),
+            layer: self.layer.unwrap_or_default(),
+            namespace: self.namespace.clone().unwrap_or_else(|| DEFAULT_NAMESPACE.to_string()),
+            anchor: self.anchor.unwrap_or_default(),
+            margins: self.margins.unwrap_or_default(),
+            opacity: self.opacity.unwrap_or(1.0),
+            bezel: self.bezel.unwrap_or(0.0),
+            output_bezels: HashMap::new(),
+        }
+    }
+}
+
+/// 配置文件 `$XDG_CONFIG_HOME/waypaper-rs/config.json`
+///
+/// ```json
+/// {
+///     "default": { "scaling": "fill", "audio": "auto", "device": "i915" },
+///     "outputs": {
+///         "DP-1": { "scaling": "fit", "background": "#202020" },
+///         "HDMI-A-1": { "layer": "overlay", "namespace": "waypaper-overlay", "opacity": 0.3, "effects": { "brightness": -0.3 } },
+///         "DP-2": { "bezel": 7.5, "hwaccel": "cuda", "device": "10de:25a0" }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// 所有壁纸的默认设置
+    pub default: WallpaperOptions,
+    /// 按输出名称覆盖的设置
+    pub outputs: HashMap<String, WallpaperOptions>,
+}
+
+impl Config {
+    /// 配置文件路径
+    pub fn path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("waypaper-rs").join("config.json"))
+    }
+
+    /// 读取配置文件，文件不存在时使用默认配置
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// 指定输出（None 表示所有输出）的设置，已合并默认设置
+    pub fn options_for(&self, output: Option<&str>) -> WallpaperOptions {
+        let output_options = output
+            .and_then(|name| self.outputs.get(name))
+            .cloned()
+            .unwrap_or_default();
+        output_options.or(&self.default)
+    }
+
+    /// 按输出名称设置的边框宽度，span 模式下每个输出使用自己的边框
+    pub fn output_bezels(&self) -> HashMap<String, f64> {
+        self.outputs
+            .iter()
+            .filter_map(|(name, options)| Some((name.clone(), options.bezel?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_example() {
+        // 与 Config 文档中的示例相同
+        let config: Config = serde_json::from_str(
+            r##"{
+                "default": { "scaling": "fill", "audio": "auto", "device": "i915" },
+                "outputs": {
+                    "DP-1": { "scaling": "fit", "background": "#202020" },
+                    "HDMI-A-1": { "layer": "overlay", "namespace": "waypaper-overlay", "opacity": 0.3, "effects": { "brightness": -0.3 } },
+                    "DP-2": { "bezel": 7.5, "hwaccel": "cuda", "device": "10de:25a0" }
+                }
+            }"##,
+        )
+        .unwrap();
+
+        assert_eq!(config.default.scaling, Some(ScalingMode::Fill));
+        assert_eq!(config.default.audio, Some(AudioOutput::Auto));
+        assert_eq!(config.default.device, Some(DeviceSelector::Driver("i915".to_string())));
+        assert_eq!(config.outputs.len(), 3);
+        assert_eq!(config.outputs["DP-1"].background, Some(Color { r: 0x20, g: 0x20, b: 0x20 }));
+        assert_eq!(config.outputs["HDMI-A-1"].layer, Some(Layer::Overlay));
+        assert_eq!(config.outputs["HDMI-A-1"].effects.as_ref().unwrap().brightness, Some(-0.3));
+        assert_eq!(config.outputs["DP-2"].hwaccel, Some(HardwareAcceleration::CUDA));
+        assert_eq!(config.outputs["DP-2"].device, Some(DeviceSelector::PciId { vendor: 0x10de, device: 0x25a0 }));
+        assert_eq!(config.output_bezels(), HashMap::from([("DP-2".to_string(), 7.5)]));
+    }
+
+    fn config() -> Config {
+        serde_json::from_str(
+            r#"{
+                "default": { "scaling": "fit", "opacity": 0.5, "effects": { "brightness": -0.2, "blur": 8 } },
+                "outputs": {
+                    "DP-1": { "scaling": "stretch", "effects": { "contrast": 1.5 } }
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_output_overrides_default() {
+        let config = config();
+        let options = config.options_for(Some("DP-1"));
+        assert_eq!(options.scaling, Some(ScalingMode::Stretch));
+        assert_eq!(options.opacity, Some(0.5));
+        // effects 整体覆盖，不继承默认设置中的亮度和模糊
+        assert_eq!(options.effects, Some(Effects { contrast: Some(1.5), ..Effects::default() }));
+
+        assert_eq!(config.options_for(Some("HDMI-A-1")), config.default);
+    }
+
+    #[test]
+    fn test_options_for_all_outputs() {
+        let config = config();
+        assert_eq!(config.options_for(None), config.default);
+    }
+}
@@ -2,6 +2,8 @@ use anyhow::{Context, Result};
 use std::io::{Read, Write};
 use std::os::unix::net::UnixStream;
 
+use crate::config::WallpaperOptions;
+use crate::wallpaper::effects::Effects;
 use crate::ipc::protocol::{IpcRequest, IpcResponse};
 
 pub struct IpcClient {
@@ -54,9 +56,14 @@ impl IpcClient {
         Ok(response)
     }
 
-    /// 设置壁纸
-    pub fn set_wallpaper(&mut self, path: String) -> Result<IpcResponse> {
-        let request = IpcRequest::SetWallpaper { path };
+    /// 设置壁纸，`output` 为空时应用到所有输出
+    pub fn set_wallpaper(
+        &mut self,
+        path: String,
+        output: Option<String>,
+        options: WallpaperOptions,
+    ) -> Result<IpcResponse> {
+        let request = IpcRequest::SetWallpaper { path, output, options };
         self.send_request(request)
     }
 
@@ -72,6 +79,24 @@ impl IpcClient {
         self.send_request(request)
     }
 
+    /// 设置音量（0.0 - 1.0）
+    pub fn set_volume(&mut self, volume: f32) -> Result<IpcResponse> {
+        let request = IpcRequest::SetVolume { volume };
+        self.send_request(request)
+    }
+
+    /// 静音或取消静音
+    pub fn set_muted(&mut self, muted: bool) -> Result<IpcResponse> {
+        let request = IpcRequest::SetMuted { muted };
+        self.send_request(request)
+    }
+
+    /// 修改画面效果，`output` 为空时应用到所有壁纸
+    pub fn set_effects(&mut self, output: Option<String>, effects: Effects) -> Result<IpcResponse> {
+        let request = IpcRequest::SetEffects { output, effects };
+        self.send_request(request)
+    }
+
     /// 关闭服务器
     pub fn shutdown(&mut self) -> Result<IpcResponse> {
         let request = IpcRequest::Shutdown;
@@ -1,14 +1,35 @@
 use serde::{Deserialize, Serialize};
 
+use crate::config::WallpaperOptions;
+use crate::wallpaper::WallpaperStatus;
+use crate::wallpaper::effects::Effects;
+
 /// IPC 请求类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IpcRequest {
-    /// 设置壁纸
-    SetWallpaper { path: String },
+    /// 设置壁纸，`output` 为空时应用到所有输出
+    SetWallpaper {
+        path: String,
+        #[serde(default)]
+        output: Option<String>,
+        /// 命令行指定的设置，优先于配置文件
+        #[serde(default)]
+        options: WallpaperOptions,
+    },
     /// 获取当前壁纸
     GetWallpaper,
     /// 获取状态
     GetStatus,
+    /// 设置音量（0.0 - 1.0）
+    SetVolume { volume: f32 },
+    /// 静音或取消静音
+    SetMuted { muted: bool },
+    /// 修改正在播放的壁纸的画面效果，`output` 为空时应用到所有壁纸
+    SetEffects {
+        #[serde(default)]
+        output: Option<String>,
+        effects: Effects,
+    },
     /// 退出服务
     Shutdown,
 }
@@ -21,7 +42,11 @@ pub enum IpcResponse {
     /// 壁纸路径响应
     WallpaperPath { path: Option<String> },
     /// 状态响应
-    Status { running: bool },
+    Status {
+        running: bool,
+        #[serde(default)]
+        wallpapers: Vec<WallpaperStatus>,
+    },
     /// 错误响应
     Error { message: String },
 }
@@ -37,8 +62,8 @@ impl IpcResponse {
         IpcResponse::WallpaperPath { path }
     }
 
-    pub fn status(running: bool) -> Self {
-        IpcResponse::Status { running }
+    pub fn status(running: bool, wallpapers: Vec<WallpaperStatus>) -> Self {
+        IpcResponse::Status { running, wallpapers }
     }
 
     pub fn error(message: impl Into<String>) -> Self {
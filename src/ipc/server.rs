@@ -6,11 +6,15 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{UnixListener, UnixStream};
 use tokio::sync::Mutex;
 
+use crate::config::Config;
 use crate::ipc::protocol::{IpcRequest, IpcResponse};
+use crate::wallpaper::effects::Effects;
+use crate::wallpaper::filter::check_filtergraph;
 use crate::wallpaper::player::Player;
 use crate::wallpaper::{Wallpaper, WallpaperType};
 use crate::wallpaper::video_hw::VideoWallpaper;
 use crate::wallpaper::project::build_project;
+use crate::wayland::OutputTarget;
 
 pub struct WayServer {
     listener: UnixListener,
@@ -93,7 +97,22 @@ async fn handle_request(
     player: &Arc<Mutex<Player>>,
 ) -> IpcResponse {
     match request {
-        IpcRequest::SetWallpaper { path } => {
+        IpcRequest::SetWallpaper { path, output, options } => {
+            // 命令行参数优先，其次是配置文件中该输出的设置和默认设置
+            let config = match Config::load() {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("Failed to load config: {:#}", e);
+                    Config::default()
+                }
+            };
+            let options = options.or(&config.options_for(output.as_deref()));
+            let target = OutputTarget::from_name(output);
+            let effects = options.effects.clone().unwrap_or_default();
+            if let Err(e) = check_effects(&effects) {
+                return IpcResponse::error(e);
+            }
+
             // 检查路径是否存在
             if !std::path::Path::new(&path).exists() {
                 return IpcResponse::error(format!("Path not found: {}", path));
@@ -120,9 +139,22 @@ async fn handle_request(
                     }
                     
                     let mut video_wallpaper = VideoWallpaper::new(video_file_path, WallpaperType::Video);
-                    // 设置性能优化参数
+                    // 设置性能优化参数；分辨率跟随输出的物理像素尺寸，不再额外限制
                     video_wallpaper.set_target_fps(30);
-                    video_wallpaper.set_max_resolution(1280, 720);
+                    {
+                        let player = player.lock().await;
+                        video_wallpaper.set_output(target.clone(), player.claimed_outputs());
+                        video_wallpaper.set_decoder_pool(player.decoder_pool());
+                    }
+                    video_wallpaper.set_audio_output(options.audio.clone().unwrap_or_default());
+                    video_wallpaper.set_hardware_acceleration(options.hwaccel.unwrap_or_default());
+                    video_wallpaper.set_decode_device(options.device.clone());
+                    let mut surface_options = options.surface_options();
+                    surface_options.output_bezels = config.output_bezels();
+                    video_wallpaper.set_surface_options(surface_options);
+                    if let Err(e) = video_wallpaper.set_effects(effects) {
+                        return IpcResponse::error(format!("Failed to set effects: {}", e));
+                    }
                     Box::new(video_wallpaper)
                 }
                 _ => {
@@ -133,12 +165,12 @@ async fn handle_request(
             // 设置到 player
             {
                 let mut player = player.lock().await;
-                player.set_wallpaper(wallpaper).await;
-                player.run().await;
+                player.set_wallpaper(target.clone(), wallpaper).await;
+                player.run(&target).await;
             }
 
-            info!("Wallpaper set: {} (type: {})", path, project.wallpaper_type);
-            IpcResponse::success(format!("Wallpaper set: {} ({})", path, project.wallpaper_type))
+            info!("Wallpaper set: {} (type: {}, output: {})", path, project.wallpaper_type, target);
+            IpcResponse::success(format!("Wallpaper set: {} ({}) on {}", path, project.wallpaper_type, target))
         }
         IpcRequest::GetWallpaper => {
             let player = player.lock().await;
@@ -148,7 +180,34 @@ async fn handle_request(
         IpcRequest::GetStatus => {
             let player = player.lock().await;
             let is_running = player.is_running().await;
-            IpcResponse::status(is_running)
+            IpcResponse::status(is_running, player.status().await)
+        }
+        IpcRequest::SetVolume { volume } => {
+            if !(0.0..=1.0).contains(&volume) {
+                return IpcResponse::error(format!("Volume must be between 0.0 and 1.0, got {}", volume));
+            }
+            let audio_control = player.lock().await.audio_control();
+            audio_control.set_volume(volume);
+            IpcResponse::success(format!("Volume: {:.0}%", volume * 100.0))
+        }
+        IpcRequest::SetMuted { muted } => {
+            let audio_control = player.lock().await.audio_control();
+            audio_control.set_muted(muted);
+            IpcResponse::success(if muted { "Muted" } else { "Unmuted" })
+        }
+        IpcRequest::SetEffects { output, effects } => {
+            if let Err(e) = check_effects(&effects) {
+                return IpcResponse::error(e);
+            }
+            let target = OutputTarget::from_name(output);
+            let mut player = player.lock().await;
+            match player.set_effects(&target, effects).await {
+                Ok(()) => {
+                    info!("Effects changed on {}", target);
+                    IpcResponse::success(format!("Effects applied on {}", target))
+                }
+                Err(e) => IpcResponse::error(format!("Failed to set effects: {}", e)),
+            }
         }
         IpcRequest::Shutdown => {
             // 停止壁纸
@@ -161,3 +220,12 @@ async fn handle_request(
         }
     }
 }
+
+/// 检查效果参数，并确认 filtergraph 能被 FFmpeg 创建，避免播放中才发现错误
+fn check_effects(effects: &Effects) -> Result<(), String> {
+    effects.validate()?;
+    match effects.filtergraph() {
+        Some(spec) => check_filtergraph(&spec).map_err(|e| format!("Invalid filter: {:#}", e)),
+        None => Ok(()),
+    }
+}
@@ -1,4 +1,5 @@
 pub mod ipc;
 pub mod cli;
 pub mod wayland;
-pub mod wallpaper;
\ No newline at end of file
+pub mod wallpaper;
+pub mod config;
\ No newline at end of file
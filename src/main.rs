@@ -2,6 +2,7 @@ mod cli;
 mod ipc;
 mod wayland;
 mod wallpaper;
+mod config;
 
 use anyhow::Result;
 use clap::Parser;
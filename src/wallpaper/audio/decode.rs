@@ -0,0 +1,167 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use ffmpeg_next as ffmpeg;
+
+use ffmpeg::ChannelLayout;
+use ffmpeg::format::input;
+use ffmpeg::format::sample::{Sample, Type as SampleType};
+use ffmpeg::media::Type;
+use ffmpeg::software::resampling;
+use ffmpeg::util::frame::audio::Audio;
+
+use super::{AudioControl, AudioOutput, CHANNELS, SAMPLE_RATE, VideoClock, apply_gain, open_sink};
+
+/// 音频落后视频超过这个时长时丢弃样本追赶
+const MAX_LATE: Duration = Duration::from_millis(80);
+/// 音频超前视频超过这个时长时等待
+const MAX_EARLY: Duration = Duration::from_millis(20);
+/// 偏差超过这个时长（视频循环、长时间遮挡后恢复）时直接 seek 到视频的位置
+const RESYNC_THRESHOLD: Duration = Duration::from_secs(1);
+/// 等待视频时的轮询间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// 输入格式，格式变化时重建重采样器
+type SourceFormat = (Sample, ChannelLayout, u32);
+
+/// 播放视频文件中的最佳音频流，以视频时钟为准保持同步，直到 `is_stopped` 被设置
+/// 阻塞调用，应在 `spawn_blocking` 中运行，不能占用 tokio worker
+pub fn play_audio(
+    video_path: &str,
+    output: &AudioOutput,
+    control: &AudioControl,
+    clock: &VideoClock,
+    is_stopped: &AtomicBool,
+) -> Result<()> {
+    ffmpeg::init().map_err(|e| anyhow::anyhow!("Failed to initialize ffmpeg: {}", e))?;
+
+    let mut ictx = input(&video_path).map_err(|e| anyhow::anyhow!("Failed to open video file: {}", e))?;
+    let Some(stream) = ictx.streams().best(Type::Audio) else {
+        info!("No audio stream in {}", video_path);
+        return Ok(());
+    };
+    let stream_index = stream.index();
+    let time_base = f64::from(stream.time_base());
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
+        .map_err(|e| anyhow::anyhow!("Failed to create decoder context: {}", e))?
+        .decoder()
+        .audio()
+        .map_err(|e| anyhow::anyhow!("Failed to create audio decoder: {}", e))?;
+    info!("Audio opened: {} Hz, {} channels", decoder.rate(), decoder.channels());
+
+    let mut sink = open_sink(output)?;
+    let mut resampler: Option<(resampling::Context, SourceFormat)> = None;
+    let mut samples: Vec<i16> = Vec::new();
+    let mut last_position = Duration::ZERO;
+
+    while !is_stopped.load(Ordering::Relaxed) {
+        let packet = match ictx.packets().next() {
+            Some((stream, packet)) if stream.index() == stream_index => packet,
+            Some(_) => continue,
+            None => {
+                // 音频比视频短时等视频循环回开头再重新开始，避免反复 seek
+                debug!("Audio ended, waiting for video to loop");
+                while !is_stopped.load(Ordering::Relaxed)
+                    && clock.now().is_none_or(|now| now + sink.latency() >= last_position)
+                {
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                let _ = ictx.seek(0, ..);
+                decoder.flush();
+                last_position = Duration::ZERO;
+                continue;
+            }
+        };
+
+        if let Err(e) = decoder.send_packet(&packet) {
+            warn!("Failed to send packet to audio decoder: {}", e);
+            continue;
+        }
+
+        let mut frame = Audio::empty();
+        while decoder.receive_frame(&mut frame).is_ok() {
+            let Some(pts) = frame.pts() else {
+                continue;
+            };
+            let position = Duration::from_secs_f64((pts as f64 * time_base).max(0.0));
+            last_position = position;
+
+            // 视频还没有显示第一帧时等待
+            let video_now = loop {
+                if is_stopped.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+                match clock.now() {
+                    Some(now) => break now,
+                    None => std::thread::sleep(POLL_INTERVAL),
+                }
+            };
+            // 现在写入的样本要在 latency 之后才会被听到
+            let heard_at = video_now + sink.latency();
+
+            if position.abs_diff(heard_at) > RESYNC_THRESHOLD {
+                debug!("Audio out of sync by {:?}, seeking to {:?}", position.abs_diff(heard_at), heard_at);
+                let ts = (heard_at.as_secs_f64() * f64::from(ffmpeg::ffi::AV_TIME_BASE)) as i64;
+                let _ = ictx.seek(ts, ..ts);
+                decoder.flush();
+                break;
+            }
+            if position + MAX_LATE < heard_at {
+                continue;
+            }
+            if position > heard_at + MAX_EARLY {
+                // 分段等待，停止时不用等到这一帧
+                let deadline = Instant::now() + (position - heard_at);
+                while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+                    if is_stopped.load(Ordering::Relaxed) {
+                        return Ok(());
+                    }
+                    std::thread::sleep(remaining.min(POLL_INTERVAL));
+                }
+            }
+
+            let source: SourceFormat = (frame.format(), source_layout(&frame), frame.rate());
+            if resampler.as_ref().is_none_or(|(_, format)| *format != source) {
+                let context = resampling::Context::get(
+                    source.0,
+                    source.1,
+                    source.2,
+                    Sample::I16(SampleType::Packed),
+                    ChannelLayout::STEREO,
+                    SAMPLE_RATE,
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to create audio resampler: {}", e))?;
+                resampler = Some((context, source));
+            }
+            let (context, _) = resampler.as_mut().unwrap();
+
+            // 预先分配足够的输出，run() 只在输出为空时按输入的样本数分配
+            let capacity = frame.samples() * SAMPLE_RATE as usize / frame.rate().max(1) as usize + 256;
+            let mut resampled = Audio::new(Sample::I16(SampleType::Packed), capacity, ChannelLayout::STEREO);
+            if let Err(e) = context.run(&frame, &mut resampled) {
+                warn!("Failed to resample audio: {}", e);
+                continue;
+            }
+
+            let len = resampled.samples() * CHANNELS as usize;
+            samples.clear();
+            samples.extend(
+                resampled.data(0)[..len * 2]
+                    .chunks_exact(2)
+                    .map(|b| i16::from_ne_bytes([b[0], b[1]])),
+            );
+            apply_gain(&mut samples, control.gain());
+            sink.write(&samples)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 部分文件没有声道布局，按声道数取默认布局
+fn source_layout(frame: &Audio) -> ChannelLayout {
+    let layout = frame.channel_layout();
+    if layout.is_empty() { ChannelLayout::default(frame.channels() as i32) } else { layout }
+}
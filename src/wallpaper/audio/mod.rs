@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+pub mod decode;
+pub mod sink;
+
+pub use decode::play_audio;
+pub use sink::{AudioSink, open_sink};
+
+/// 输出采样率
+pub const SAMPLE_RATE: u32 = 48000;
+/// 输出声道数（交错的 S16LE）
+pub const CHANNELS: u16 = 2;
+
+/// 音频输出方式
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum AudioOutput {
+    /// 不播放声音
+    #[default]
+    Off,
+    /// 依次尝试 PipeWire、PulseAudio、ALSA
+    Auto,
+    PipeWire,
+    PulseAudio,
+    Alsa,
+    /// 丢弃样本，按实时速度消耗，用于测试
+    Null,
+    /// 写入 WAV 文件，用于测试
+    Wav(PathBuf),
+}
+
+impl FromStr for AudioOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("wav:") {
+            return Ok(AudioOutput::Wav(PathBuf::from(path)));
+        }
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Ok(AudioOutput::Off),
+            "auto" => Ok(AudioOutput::Auto),
+            "pipewire" => Ok(AudioOutput::PipeWire),
+            "pulseaudio" | "pulse" => Ok(AudioOutput::PulseAudio),
+            "alsa" => Ok(AudioOutput::Alsa),
+            "null" => Ok(AudioOutput::Null),
+            _ => Err(format!(
+                "unknown audio output: {} (expected off, auto, pipewire, pulseaudio, alsa, null or wav:<path>)",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for AudioOutput {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<AudioOutput> for String {
+    fn from(output: AudioOutput) -> Self {
+        output.to_string()
+    }
+}
+
+impl std::fmt::Display for AudioOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioOutput::Off => write!(f, "off"),
+            AudioOutput::Auto => write!(f, "auto"),
+            AudioOutput::PipeWire => write!(f, "pipewire"),
+            AudioOutput::PulseAudio => write!(f, "pulseaudio"),
+            AudioOutput::Alsa => write!(f, "alsa"),
+            AudioOutput::Null => write!(f, "null"),
+            AudioOutput::Wav(path) => write!(f, "wav:{}", path.display()),
+        }
+    }
+}
+
+/// 全局音量和静音状态，所有音频管线共用，可通过 IPC 修改
+#[derive(Debug, Clone)]
+pub struct AudioControl {
+    /// f32 音量的位表示
+    volume: Arc<AtomicU32>,
+    muted: Arc<AtomicBool>,
+}
+
+impl Default for AudioControl {
+    fn default() -> Self {
+        Self {
+            volume: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+            muted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl AudioControl {
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    /// 设置音量（0.0 - 1.0）
+    pub fn set_volume(&self, volume: f32) {
+        self.volume.store(volume.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        self.muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// 实际施加到样本上的增益
+    pub fn gain(&self) -> f32 {
+        if self.is_muted() { 0.0 } else { self.volume() }
+    }
+}
+
+/// 按增益缩放 S16 样本
+pub fn apply_gain(samples: &mut [i16], gain: f32) {
+    if gain >= 1.0 {
+        return;
+    }
+    for sample in samples {
+        *sample = (*sample as f32 * gain).round() as i16;
+    }
+}
+
+/// 视频播放位置，由渲染任务在显示帧后更新，音频以它为准同步
+#[derive(Debug, Clone, Default)]
+pub struct VideoClock {
+    position: Arc<std::sync::Mutex<Option<(Duration, Instant)>>>,
+}
+
+impl VideoClock {
+    /// 视频停止更新（被遮挡、暂停）后最多外推这么久，之后音频等待视频
+    const MAX_EXTRAPOLATION: Duration = Duration::from_millis(500);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录刚显示的帧的媒体时间
+    pub fn update(&self, position: Duration) {
+        *self.position.lock().unwrap() = Some((position, Instant::now()));
+    }
+
+    /// 当前的视频媒体时间，视频尚未开始时为 None
+    pub fn now(&self) -> Option<Duration> {
+        let (position, updated) = (*self.position.lock().unwrap())?;
+        Some(position + updated.elapsed().min(Self::MAX_EXTRAPOLATION))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_audio_output() {
+        assert_eq!("pulse".parse::<AudioOutput>(), Ok(AudioOutput::PulseAudio));
+        assert_eq!(
+            "wav:/tmp/out.wav".parse::<AudioOutput>(),
+            Ok(AudioOutput::Wav(PathBuf::from("/tmp/out.wav")))
+        );
+        assert_eq!(AudioOutput::Wav(PathBuf::from("/tmp/out.wav")).to_string(), "wav:/tmp/out.wav");
+        assert!("jack".parse::<AudioOutput>().is_err());
+    }
+
+    #[test]
+    fn test_gain() {
+        let control = AudioControl::default();
+        control.set_volume(0.5);
+        let mut samples = [1000i16, -1000, i16::MAX];
+        apply_gain(&mut samples, control.gain());
+        assert_eq!(samples, [500, -500, 16384]);
+
+        control.set_muted(true);
+        assert_eq!(control.gain(), 0.0);
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use super::{AudioOutput, CHANNELS, SAMPLE_RATE};
+
+/// 每秒的字节数（S16LE 交错）
+const BYTES_PER_SECOND: u32 = SAMPLE_RATE * CHANNELS as u32 * 2;
+/// 播放器内部的缓冲时长
+const PLAYER_LATENCY: Duration = Duration::from_millis(50);
+/// 到播放器的管道大小，越小 A/V 同步越准
+const PIPE_SIZE: i32 = 16 * 1024;
+
+/// 音频输出，接收 SAMPLE_RATE / CHANNELS 的交错 S16 样本
+/// `write` 会按播放速度阻塞，只能在阻塞线程（`spawn_blocking`）中调用，不能在 tokio worker 上调用
+pub trait AudioSink: Send {
+    /// 写入样本，设备缓冲区满时阻塞，因此写入速度就是播放速度
+    fn write(&mut self, samples: &[i16]) -> Result<()>;
+
+    /// 已写入但还没有被听到的时长
+    fn latency(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// 打开音频输出
+pub fn open_sink(output: &AudioOutput) -> Result<Box<dyn AudioSink>> {
+    match output {
+        AudioOutput::Off => Err(anyhow::anyhow!("Audio output is off")),
+        AudioOutput::Auto => {
+            for backend in [AudioOutput::PipeWire, AudioOutput::PulseAudio, AudioOutput::Alsa] {
+                match open_sink(&backend) {
+                    Ok(sink) => return Ok(sink),
+                    Err(e) => warn!("Audio output {} unavailable: {:#}", backend, e),
+                }
+            }
+            Err(anyhow::anyhow!("No audio output available"))
+        }
+        AudioOutput::PipeWire => CommandSink::spawn(
+            "pw-cat",
+            &[
+                "--playback".to_string(),
+                "--format=s16".to_string(),
+                format!("--rate={}", SAMPLE_RATE),
+                format!("--channels={}", CHANNELS),
+                format!("--latency={}ms", PLAYER_LATENCY.as_millis()),
+                "--media-role=Movie".to_string(),
+                "-".to_string(),
+            ],
+        ),
+        AudioOutput::PulseAudio => CommandSink::spawn(
+            "pacat",
+            &[
+                "--playback".to_string(),
+                "--format=s16le".to_string(),
+                format!("--rate={}", SAMPLE_RATE),
+                format!("--channels={}", CHANNELS),
+                format!("--latency-msec={}", PLAYER_LATENCY.as_millis()),
+                "--client-name=waypaper-rs".to_string(),
+            ],
+        ),
+        AudioOutput::Alsa => CommandSink::spawn(
+            "aplay",
+            &[
+                "--quiet".to_string(),
+                "--file-type=raw".to_string(),
+                "--format=S16_LE".to_string(),
+                format!("--rate={}", SAMPLE_RATE),
+                format!("--channels={}", CHANNELS),
+                format!("--buffer-time={}", PLAYER_LATENCY.as_micros()),
+            ],
+        ),
+        AudioOutput::Null => Ok(Box::new(NullSink::new())),
+        AudioOutput::Wav(path) => Ok(Box::new(WavSink::create(path)?)),
+    }
+}
+
+/// 把原始 PCM 通过管道交给 pw-cat / pacat / aplay 播放
+struct CommandSink {
+    child: Child,
+    stdin: ChildStdin,
+    buffer: Vec<u8>,
+    /// 管道实际的容量，缩小失败时为系统默认值
+    pipe_size: usize,
+}
+
+impl CommandSink {
+    fn spawn(program: &str, args: &[String]) -> Result<Box<dyn AudioSink>> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start {}", program))?;
+        let stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("{} has no stdin", program))?;
+        // 缩小管道，避免数百毫秒的样本堆积在管道里
+        let fd = stdin.as_raw_fd();
+        if unsafe { libc::fcntl(fd, libc::F_SETPIPE_SZ, PIPE_SIZE) } < 0 {
+            warn!(
+                "Failed to shrink audio pipe to {} bytes, A/V sync uses the default size: {}",
+                PIPE_SIZE,
+                std::io::Error::last_os_error()
+            );
+        }
+        // 按实际容量计算延迟；内核可能把大小向上取整
+        let pipe_size = match unsafe { libc::fcntl(fd, libc::F_GETPIPE_SZ) } {
+            size if size > 0 => size as usize,
+            _ => {
+                warn!("Failed to query audio pipe size: {}", std::io::Error::last_os_error());
+                PIPE_SIZE as usize
+            }
+        };
+        info!("Audio output: {} (pipe {} bytes)", program, pipe_size);
+        Ok(Box::new(Self { child, stdin, buffer: Vec::new(), pipe_size }))
+    }
+}
+
+impl AudioSink for CommandSink {
+    fn write(&mut self, samples: &[i16]) -> Result<()> {
+        self.buffer.clear();
+        self.buffer.extend(samples.iter().flat_map(|s| s.to_le_bytes()));
+        self.stdin.write_all(&self.buffer).context("Audio player exited")
+    }
+
+    fn latency(&self) -> Duration {
+        PLAYER_LATENCY + Duration::from_secs_f64(self.pipe_size as f64 / BYTES_PER_SECOND as f64)
+    }
+}
+
+impl Drop for CommandSink {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// 丢弃样本，但按实时速度阻塞（`std::thread::sleep`），行为与真实设备一致
+pub struct NullSink {
+    start: Option<Instant>,
+    written: u64,
+}
+
+impl NullSink {
+    pub fn new() -> Self {
+        Self { start: None, written: 0 }
+    }
+}
+
+impl Default for NullSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioSink for NullSink {
+    fn write(&mut self, samples: &[i16]) -> Result<()> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        self.written += (samples.len() / CHANNELS as usize) as u64;
+        let due = start + Duration::from_secs_f64(self.written as f64 / SAMPLE_RATE as f64);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+        Ok(())
+    }
+}
+
+/// 写入 WAV 文件，不做实时限速
+pub struct WavSink {
+    writer: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavSink {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut sink = Self { writer: BufWriter::new(file), data_len: 0 };
+        sink.write_header()?;
+        info!("Audio output: {}", path.display());
+        Ok(sink)
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let block_align = CHANNELS * 2;
+        let w = &mut self.writer;
+        w.write_all(b"RIFF")?;
+        w.write_all(&(36 + self.data_len).to_le_bytes())?;
+        w.write_all(b"WAVEfmt ")?;
+        w.write_all(&16u32.to_le_bytes())?;
+        w.write_all(&1u16.to_le_bytes())?; // PCM
+        w.write_all(&CHANNELS.to_le_bytes())?;
+        w.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        w.write_all(&BYTES_PER_SECOND.to_le_bytes())?;
+        w.write_all(&block_align.to_le_bytes())?;
+        w.write_all(&16u16.to_le_bytes())?; // bits per sample
+        w.write_all(b"data")?;
+        w.write_all(&self.data_len.to_le_bytes())?;
+        Ok(())
+    }
+
+    /// 回填 RIFF 和 data 块的长度
+    pub fn finish(&mut self) -> Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl AudioSink for WavSink {
+    fn write(&mut self, samples: &[i16]) -> Result<()> {
+        for sample in samples {
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_len = self.data_len.saturating_add((samples.len() * 2) as u32);
+        Ok(())
+    }
+}
+
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            warn!("Failed to finish WAV file: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wav_sink_header() {
+        let path = std::env::temp_dir().join(format!("waypaper-test-{}.wav", std::process::id()));
+        {
+            let mut sink = WavSink::create(&path).unwrap();
+            sink.write(&[1, -1, 2, -2]).unwrap();
+        }
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data.len(), 44 + 8);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()), 36 + 8);
+        assert_eq!(u32::from_le_bytes(data[40..44].try_into().unwrap()), 8);
+        assert_eq!(&data[44..46], &1i16.to_le_bytes());
+    }
+}
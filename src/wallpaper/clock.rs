@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+
+/// 一帧的显示安排
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSchedule {
+    /// 在指定时刻显示
+    Present(Instant),
+    /// 已经错过显示时间，丢弃
+    Drop,
+}
+
+/// 按 PTS 安排帧的显示时间
+/// 把视频时间轴映射到墙上时间，`origin` 是 PTS 为 0 的帧应当显示的时刻；
+/// 视频循环或 seek 后 generation 改变，时间轴重新对齐
+#[derive(Debug, Default)]
+pub struct PlaybackClock {
+    /// (generation, origin)
+    anchor: Option<(u64, Instant)>,
+    dropped_in_row: u32,
+}
+
+impl PlaybackClock {
+    /// 晚于显示时间超过这个时长的帧被丢弃
+    const MAX_LATE: Duration = Duration::from_millis(50);
+    /// 偏差超过这个时长（暂停、长时间遮挡、时间戳跳变）时不再追赶，从当前帧重新对齐
+    const RESYNC_THRESHOLD: Duration = Duration::from_secs(1);
+    /// 连续丢弃这么多帧后仍然显示一帧，避免渲染跟不上时画面完全停止
+    const MAX_CONSECUTIVE_DROPS: u32 = 8;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 计算 `generation` 中 PTS 为 `pts` 的帧的显示时间
+    pub fn schedule(&mut self, generation: u64, pts: Duration, now: Instant) -> FrameSchedule {
+        let origin = match self.anchor {
+            Some((anchor_generation, origin)) if anchor_generation == generation => origin,
+            _ => return self.present_now(generation, pts, now),
+        };
+
+        let due = origin + pts;
+        if due > now + Self::RESYNC_THRESHOLD {
+            return self.present_now(generation, pts, now);
+        }
+
+        let late = now.saturating_duration_since(due);
+        if late > Self::RESYNC_THRESHOLD {
+            return self.present_now(generation, pts, now);
+        }
+        if late > Self::MAX_LATE {
+            if self.dropped_in_row < Self::MAX_CONSECUTIVE_DROPS {
+                self.dropped_in_row += 1;
+                return FrameSchedule::Drop;
+            }
+            return self.present_now(generation, pts, now);
+        }
+
+        self.dropped_in_row = 0;
+        FrameSchedule::Present(due)
+    }
+
+    /// 让时间轴从这一帧重新开始，立即显示
+    fn present_now(&mut self, generation: u64, pts: Duration, now: Instant) -> FrameSchedule {
+        let origin = now.checked_sub(pts).unwrap_or(now);
+        self.anchor = Some((generation, origin));
+        self.dropped_in_row = 0;
+        FrameSchedule::Present(now)
+    }
+
+    /// 清除对齐，下一帧立即显示（重连、输出恢复后）
+    pub fn reset(&mut self) {
+        self.anchor = None;
+        self.dropped_in_row = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_schedule_by_pts() {
+        let start = Instant::now();
+        let mut clock = PlaybackClock::new();
+
+        assert_eq!(clock.schedule(0, ms(1000), start), FrameSchedule::Present(start));
+        // 可变帧率：间隔按 PTS 而不是固定的帧时长
+        assert_eq!(clock.schedule(0, ms(1040), start), FrameSchedule::Present(start + ms(40)));
+        assert_eq!(clock.schedule(0, ms(1050), start + ms(20)), FrameSchedule::Present(start + ms(50)));
+        // 迟到不多时仍然显示
+        assert_eq!(clock.schedule(0, ms(1100), start + ms(130)), FrameSchedule::Present(start + ms(100)));
+        // 迟到太多时丢弃
+        assert_eq!(clock.schedule(0, ms(1133), start + ms(300)), FrameSchedule::Drop);
+        assert_eq!(clock.schedule(0, ms(1300), start + ms(310)), FrameSchedule::Present(start + ms(300)));
+    }
+
+    #[test]
+    fn test_generation_and_resync() {
+        let start = Instant::now();
+        let mut clock = PlaybackClock::new();
+
+        clock.schedule(0, ms(5000), start);
+        // 视频循环：新的 generation 从 0 开始，立即显示
+        let now = start + ms(33);
+        assert_eq!(clock.schedule(1, ms(0), now), FrameSchedule::Present(now));
+        assert_eq!(clock.schedule(1, ms(33), now), FrameSchedule::Present(now + ms(33)));
+
+        // 长时间遮挡后从当前帧重新开始，而不是丢弃积压的帧
+        let later = now + Duration::from_secs(10);
+        assert_eq!(clock.schedule(1, ms(66), later), FrameSchedule::Present(later));
+        assert_eq!(clock.schedule(1, ms(99), later), FrameSchedule::Present(later + ms(33)));
+    }
+
+    #[test]
+    fn test_consecutive_drops_are_bounded() {
+        let start = Instant::now();
+        let mut clock = PlaybackClock::new();
+        clock.schedule(0, ms(0), start);
+
+        // 渲染跟不上：丢弃有上限，之后从当前帧重新对齐
+        let now = start + ms(500);
+        for i in 1..=PlaybackClock::MAX_CONSECUTIVE_DROPS as u64 {
+            assert_eq!(clock.schedule(0, ms(i * 10), now), FrameSchedule::Drop);
+        }
+        assert_eq!(clock.schedule(0, ms(90), now), FrameSchedule::Present(now));
+        assert_eq!(clock.schedule(0, ms(100), now), FrameSchedule::Present(now + ms(10)));
+    }
+}
@@ -0,0 +1,454 @@
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::{Mutex, mpsc, watch};
+
+use crate::wallpaper::audio::{AudioControl, AudioOutput, VideoClock, play_audio};
+use crate::wallpaper::devices::DeviceSelector;
+use crate::wallpaper::hwaccel::HardwareAcceleration;
+use crate::wallpaper::video_hw::{FrameData, RenderHints, decode_video_async};
+use crate::wayland::{ColorQuality, ScalingFilter, ScalingMode};
+
+/// 等待某个渲染任务接收帧的最长时间，超时后认为它停滞（输出被遮挡或断开），之后不再等待它
+const STALL_TIMEOUT: Duration = Duration::from_millis(200);
+/// 没有新帧时刷新渲染提示和订阅者列表的间隔
+const HINTS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 解码参数，参数完全相同的壁纸共用一个解码器
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DecoderKey {
+    pub video_path: String,
+    pub hw_accel_type: HardwareAcceleration,
+    pub device: Option<DeviceSelector>,
+    pub target_fps: u32,
+    pub max_width: u32,
+    pub max_height: u32,
+    pub scaling: ScalingMode,
+    pub quality: ColorQuality,
+    pub scaling_filter: ScalingFilter,
+    pub audio: AudioOutput,
+    /// 画面效果的 filtergraph，播放中修改时解码器在池中换到新的参数下
+    pub filter: Option<String>,
+}
+
+/// 解码器实际使用的后端，例如 "vaapi (/dev/dri/renderD128)"；解码器打开前为 None
+pub type DecoderBackend = Arc<std::sync::Mutex<Option<String>>>;
+
+/// 订阅者当前所在的解码器，修改画面效果时订阅者可能被移到另一个解码器
+#[derive(Clone, Default)]
+pub struct DecoderLink {
+    pub decoder_id: u64,
+    /// 渲染任务显示帧后更新，音频据此同步
+    pub clock: VideoClock,
+    pub backend: DecoderBackend,
+}
+
+pub type SharedLink = Arc<std::sync::Mutex<DecoderLink>>;
+
+/// 一次订阅：帧通道、渲染提示和当前所在的解码器
+pub struct Subscription {
+    /// 订阅者 ID，用于在播放中修改画面效果
+    pub subscriber_id: u64,
+    pub frames: mpsc::Receiver<Arc<FrameData>>,
+    pub hints: watch::Sender<RenderHints>,
+    pub link: SharedLink,
+}
+
+/// 一个渲染任务
+#[derive(Clone)]
+struct Subscriber {
+    id: u64,
+    tx: mpsc::Sender<Arc<FrameData>>,
+    hints: watch::Receiver<RenderHints>,
+    stalled: Arc<AtomicBool>,
+    link: SharedLink,
+}
+
+struct SharedDecoder {
+    id: u64,
+    subscribers: Arc<std::sync::Mutex<Vec<Subscriber>>>,
+    clock: VideoClock,
+    backend: DecoderBackend,
+    filter: watch::Sender<Option<String>>,
+}
+
+impl SharedDecoder {
+    fn link(&self) -> DecoderLink {
+        DecoderLink { decoder_id: self.id, clock: self.clock.clone(), backend: self.backend.clone() }
+    }
+}
+
+#[derive(Default)]
+struct PoolInner {
+    decoders: HashMap<DecoderKey, SharedDecoder>,
+    next_id: u64,
+}
+
+/// 正在运行的解码器，同一个视频显示在多个输出上时只解码一次，帧通过引用计数分发给每个渲染任务
+/// 开启音频时每个解码器对应一路音频
+#[derive(Clone, Default)]
+pub struct DecoderPool {
+    inner: Arc<std::sync::Mutex<PoolInner>>,
+    audio_control: AudioControl,
+}
+
+impl DecoderPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 所有音频共用的音量和静音状态
+    pub fn audio_control(&self) -> AudioControl {
+        self.audio_control.clone()
+    }
+
+    /// 订阅解码器的帧，没有相同参数的解码器时启动一个
+    /// 帧通道被丢弃后自动退订，最后一个订阅者退出时解码器停止
+    pub fn subscribe(&self, key: DecoderKey, queue_frames: usize) -> Subscription {
+        let (tx, rx) = mpsc::channel(queue_frames);
+        let (hints_tx, hints_rx) = watch::channel(RenderHints::default());
+        let link = SharedLink::default();
+
+        let mut inner = self.inner.lock().unwrap();
+        let subscriber_id = inner.next_id;
+        inner.next_id += 1;
+        let subscriber = Subscriber {
+            id: subscriber_id,
+            tx,
+            hints: hints_rx,
+            stalled: Arc::new(AtomicBool::new(false)),
+            link: link.clone(),
+        };
+        self.attach(&mut inner, key, subscriber);
+        Subscription { subscriber_id, frames: rx, hints: hints_tx, link }
+    }
+
+    /// 把订阅者加入参数相同的解码器，没有时启动一个
+    fn attach(&self, inner: &mut PoolInner, key: DecoderKey, subscriber: Subscriber) {
+        if let Some(decoder) = inner.decoders.get(&key) {
+            *subscriber.link.lock().unwrap() = decoder.link();
+            let mut subscribers = decoder.subscribers.lock().unwrap();
+            subscribers.push(subscriber);
+            info!("Sharing decoder for {} ({} subscribers)", key.video_path, subscribers.len());
+            return;
+        }
+
+        let id = inner.next_id;
+        inner.next_id += 1;
+        let queue_frames = subscriber.tx.max_capacity();
+        let decoder = SharedDecoder {
+            id,
+            subscribers: Arc::new(std::sync::Mutex::new(Vec::new())),
+            clock: VideoClock::new(),
+            backend: DecoderBackend::default(),
+            filter: watch::Sender::new(key.filter.clone()),
+        };
+        *subscriber.link.lock().unwrap() = decoder.link();
+        decoder.subscribers.lock().unwrap().push(subscriber);
+        // 持有池的锁启动解码任务，解码器结束时的移除一定发生在插入之后
+        self.spawn_decoder(key.clone(), &decoder, queue_frames);
+        inner.decoders.insert(key, decoder);
+    }
+
+    fn spawn_decoder(&self, key: DecoderKey, decoder: &SharedDecoder, queue_frames: usize) {
+        let id = decoder.id;
+        let subscribers = decoder.subscribers.clone();
+        let clock = decoder.clock.clone();
+        let backend = decoder.backend.clone();
+        let filter_rx = decoder.filter.subscribe();
+        let (frame_tx, frame_rx) = mpsc::channel::<FrameData>(queue_frames);
+        let (hints_tx, hints_rx) = watch::channel(RenderHints::default());
+        let is_paused = Arc::new(Mutex::new(false));
+        let is_stopped = Arc::new(Mutex::new(false));
+
+        info!("Starting decoder for {}", key.video_path);
+        let decode_key = key.clone();
+        let decode_stopped = is_stopped.clone();
+        tokio::spawn(async move {
+            if let Err(e) = decode_video_async(decode_key, frame_tx, hints_rx, filter_rx, is_paused, decode_stopped, backend).await {
+                error!("Video decode error: {}", e);
+            }
+        });
+
+        // 音频在阻塞线程池中写入输出设备（写入会按播放速度阻塞），跟随视频时钟
+        let audio_stopped = Arc::new(AtomicBool::new(false));
+        if key.audio != AudioOutput::Off {
+            let audio_key = key.clone();
+            let control = self.audio_control.clone();
+            let stopped = audio_stopped.clone();
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = play_audio(&audio_key.video_path, &audio_key.audio, &control, &clock, &stopped) {
+                    error!("Audio playback error: {:#}", e);
+                }
+            });
+        }
+
+        let pool = self.clone();
+        tokio::spawn(async move {
+            fan_out(frame_rx, hints_tx, &subscribers, || pool.remove_if_unused(id, &subscribers)).await;
+            // 解码结束（出错、打开失败）时也要移除，否则之后的订阅会共用一个不再产生帧的解码器
+            pool.remove(id, &subscribers);
+            // 关闭帧通道后解码任务会在下一次 send 时退出
+            *is_stopped.lock().await = true;
+            audio_stopped.store(true, Ordering::Relaxed);
+            info!("Decoder for {} stopped", key.video_path);
+        });
+    }
+
+    /// 修改一个订阅者的画面效果
+    /// 只有它在用解码器时直接修改，不重新开始播放；否则把它移到参数相同的解码器（没有时启动一个），不影响其他输出
+    pub fn set_filter(&self, subscriber_id: u64, filter: Option<String>) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(key) = inner
+            .decoders
+            .iter()
+            .find(|(_, d)| d.subscribers.lock().unwrap().iter().any(|s| s.id == subscriber_id))
+            .map(|(key, _)| key.clone())
+        else {
+            return;
+        };
+        let new_key = DecoderKey { filter, ..key.clone() };
+        if new_key == key {
+            return;
+        }
+
+        let decoder = &inner.decoders[&key];
+        let sole = decoder.subscribers.lock().unwrap().len() == 1;
+        if sole && !inner.decoders.contains_key(&new_key) {
+            let decoder = inner.decoders.remove(&key).unwrap();
+            decoder.filter.send_replace(new_key.filter.clone());
+            inner.decoders.insert(new_key, decoder);
+            return;
+        }
+
+        let (subscriber, empty) = {
+            let mut subscribers = decoder.subscribers.lock().unwrap();
+            let index = subscribers.iter().position(|s| s.id == subscriber_id).unwrap();
+            let subscriber = subscribers.remove(index);
+            (subscriber, subscribers.is_empty())
+        };
+        if empty {
+            // 已经没有订阅者，分发任务下一次检查时停止解码
+            inner.decoders.remove(&key);
+        }
+        subscriber.stalled.store(false, Ordering::Relaxed);
+        self.attach(&mut inner, new_key, subscriber);
+    }
+
+    /// 没有订阅者时从池中移除解码器；持有池的锁，避免与新的订阅竞争
+    /// 按 ID 查找，解码器的参数可能已经在播放中修改
+    fn remove_if_unused(&self, id: u64, subscribers: &std::sync::Mutex<Vec<Subscriber>>) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if !subscribers.lock().unwrap().is_empty() {
+            return false;
+        }
+        inner.decoders.retain(|_, d| d.id != id);
+        true
+    }
+
+    /// 无条件移除解码器，并关闭所有订阅者的帧通道，让渲染任务知道解码已经结束
+    fn remove(&self, id: u64, subscribers: &std::sync::Mutex<Vec<Subscriber>>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.decoders.retain(|_, d| d.id != id);
+        subscribers.lock().unwrap().clear();
+    }
+}
+
+/// 把解码器的帧分发给所有订阅者，直到解码结束或没有订阅者
+async fn fan_out(
+    mut frame_rx: mpsc::Receiver<FrameData>,
+    hints_tx: watch::Sender<RenderHints>,
+    subscribers: &std::sync::Mutex<Vec<Subscriber>>,
+    remove_if_unused: impl Fn() -> bool,
+) {
+    loop {
+        let snapshot = subscribers.lock().unwrap().clone();
+        if snapshot.is_empty() && remove_if_unused() {
+            return;
+        }
+        let merged = merge_hints(&snapshot);
+        hints_tx.send_if_modified(|hints| {
+            if *hints != merged {
+                *hints = merged;
+                true
+            } else {
+                false
+            }
+        });
+
+        let frame = tokio::select! {
+            frame = frame_rx.recv() => match frame {
+                Some(frame) => Arc::new(frame),
+                None => return,
+            },
+            _ = tokio::time::sleep(HINTS_INTERVAL) => continue,
+        };
+
+        let mut any_closed = false;
+        let all_stalled = snapshot.iter().all(|s| s.stalled.load(Ordering::Relaxed));
+        for (index, subscriber) in snapshot.iter().enumerate() {
+            if subscriber.stalled.load(Ordering::Relaxed) && !(all_stalled && index == 0) {
+                // 停滞的订阅者只在通道有空位时接收，不拖慢其他输出
+                match subscriber.tx.try_send(frame.clone()) {
+                    Ok(()) => subscriber.stalled.store(false, Ordering::Relaxed),
+                    Err(mpsc::error::TrySendError::Full(_)) => {}
+                    Err(mpsc::error::TrySendError::Closed(_)) => any_closed = true,
+                }
+                continue;
+            }
+            // 所有订阅者都停滞时阻塞在第一个上，和单个输出时一样让解码器暂停
+            let result = if all_stalled {
+                Ok(subscriber.tx.send(frame.clone()).await)
+            } else {
+                tokio::time::timeout(STALL_TIMEOUT, subscriber.tx.send(frame.clone())).await
+            };
+            match result {
+                Ok(Ok(())) => subscriber.stalled.store(false, Ordering::Relaxed),
+                Ok(Err(_)) => any_closed = true,
+                Err(_) => {
+                    warn!("Render task is not keeping up, no longer waiting for it");
+                    subscriber.stalled.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+
+        if any_closed {
+            subscribers.lock().unwrap().retain(|s| !s.tx.is_closed());
+        }
+    }
+}
+
+/// 合并所有订阅者的渲染提示：按最大的输出缩放，只使用所有连接都能导入的 DMA-BUF 格式
+fn merge_hints(subscribers: &[Subscriber]) -> RenderHints {
+    let mut hints: Vec<RenderHints> = subscribers.iter().map(|s| s.hints.borrow().clone()).collect();
+    let Some(mut merged) = hints.pop() else {
+        return RenderHints::default();
+    };
+    for other in &hints {
+        merged.target_size = match (merged.target_size, other.target_size) {
+            (Some((w1, h1)), Some((w2, h2))) => Some((w1.max(w2), h1.max(h2))),
+            (a, b) => a.or(b),
+        };
+        merged.dmabuf_formats.retain(|format| other.dmabuf_formats.contains(format));
+    }
+    merged
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(video_path: &str) -> DecoderKey {
+        DecoderKey {
+            video_path: video_path.to_string(),
+            hw_accel_type: HardwareAcceleration::default(),
+            device: None,
+            target_fps: 30,
+            max_width: 0,
+            max_height: 0,
+            scaling: ScalingMode::default(),
+            quality: ColorQuality::default(),
+            scaling_filter: ScalingFilter::default(),
+            audio: AudioOutput::Off,
+            filter: None,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_closed_decoder_is_removed() {
+        let pool = DecoderPool::new();
+        // 文件不存在，解码任务立即结束并关闭帧通道
+        let mut subscription = pool.subscribe(key("/nonexistent/video.mp4"), 4);
+        let frame = tokio::time::timeout(Duration::from_secs(5), subscription.frames.recv()).await;
+        assert!(matches!(frame, Ok(None)));
+        assert!(pool.inner.lock().unwrap().decoders.is_empty());
+
+        let resubscribed = pool.subscribe(key("/nonexistent/video.mp4"), 4);
+        assert_ne!(resubscribed.link.lock().unwrap().decoder_id, subscription.link.lock().unwrap().decoder_id);
+    }
+
+    fn insert_decoder(pool: &DecoderPool, key: DecoderKey, subscriber_ids: &[u64]) -> u64 {
+        let mut inner = pool.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        let decoder = SharedDecoder {
+            id,
+            subscribers: Arc::new(std::sync::Mutex::new(Vec::new())),
+            clock: VideoClock::new(),
+            backend: DecoderBackend::default(),
+            filter: watch::Sender::new(key.filter.clone()),
+        };
+        for &subscriber_id in subscriber_ids {
+            let (tx, _) = mpsc::channel(4);
+            let (_, hints) = watch::channel(RenderHints::default());
+            decoder.subscribers.lock().unwrap().push(Subscriber {
+                id: subscriber_id,
+                tx,
+                hints,
+                stalled: Arc::new(AtomicBool::new(false)),
+                link: Arc::new(std::sync::Mutex::new(decoder.link())),
+            });
+        }
+        inner.decoders.insert(key, decoder);
+        id
+    }
+
+    /// 以某个参数运行的解码器的 (ID, 订阅者 ID)
+    fn decoder_for(pool: &DecoderPool, key: &DecoderKey) -> Option<(u64, Vec<u64>)> {
+        let inner = pool.inner.lock().unwrap();
+        let decoder = inner.decoders.get(key)?;
+        let subscribers = decoder.subscribers.lock().unwrap().iter().map(|s| s.id).collect();
+        Some((decoder.id, subscribers))
+    }
+
+    fn link_of(pool: &DecoderPool, key: &DecoderKey, subscriber_id: u64) -> u64 {
+        let inner = pool.inner.lock().unwrap();
+        let subscribers = inner.decoders[key].subscribers.lock().unwrap();
+        let subscriber = subscribers.iter().find(|s| s.id == subscriber_id).unwrap();
+        subscriber.link.lock().unwrap().decoder_id
+    }
+
+    // 单线程运行时：测试中不 await，启动的解码任务不会运行
+    #[tokio::test]
+    async fn test_set_filter_on_shared_decoder() {
+        let pool = DecoderPool::new();
+        let plain = key("video.mp4");
+        let flipped = DecoderKey { filter: Some("hflip".to_string()), ..plain.clone() };
+        let shared = insert_decoder(&pool, plain.clone(), &[100, 101]);
+
+        // 其他输出还在用这个解码器：只移走修改效果的订阅者
+        pool.set_filter(100, Some("hflip".to_string()));
+        // 不在任何解码器中的订阅者被忽略
+        pool.set_filter(999, None);
+        assert_eq!(decoder_for(&pool, &plain), Some((shared, vec![101])));
+        let (moved_to, subscribers) = decoder_for(&pool, &flipped).unwrap();
+        assert_ne!(moved_to, shared);
+        assert_eq!(subscribers, vec![100]);
+        assert_eq!(link_of(&pool, &flipped, 100), moved_to);
+
+        // 只有一个订阅者时原地修改
+        pool.set_filter(101, Some("vflip".to_string()));
+        let vflipped = DecoderKey { filter: Some("vflip".to_string()), ..plain.clone() };
+        assert_eq!(decoder_for(&pool, &plain), None);
+        assert_eq!(decoder_for(&pool, &vflipped), Some((shared, vec![101])));
+        assert_eq!(*pool.inner.lock().unwrap().decoders[&vflipped].filter.borrow(), Some("vflip".to_string()));
+    }
+
+    #[test]
+    fn test_set_filter_joins_existing_decoder() {
+        let pool = DecoderPool::new();
+        let plain = key("video.mp4");
+        let flipped = DecoderKey { filter: Some("hflip".to_string()), ..plain.clone() };
+        insert_decoder(&pool, plain.clone(), &[100]);
+        let existing = insert_decoder(&pool, flipped.clone(), &[101]);
+
+        // 已有相同参数的解码器：即使是唯一的订阅者也移过去，空下来的解码器被移除
+        pool.set_filter(100, Some("hflip".to_string()));
+        assert_eq!(decoder_for(&pool, &plain), None);
+        assert_eq!(decoder_for(&pool, &flipped), Some((existing, vec![101, 100])));
+        assert_eq!(link_of(&pool, &flipped, 100), existing);
+        assert_eq!(pool.inner.lock().unwrap().decoders.len(), 1);
+    }
+}
@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// DRM render node 所在目录
+const DEV_DRI: &str = "/dev/dri";
+/// sysfs 中的 DRM 设备，`<node>/device` 指向 PCI 设备
+const SYS_CLASS_DRM: &str = "/sys/class/drm";
+
+/// 一个 DRM render node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderNode {
+    /// 设备路径，例如 /dev/dri/renderD128
+    pub path: PathBuf,
+    /// 内核驱动，例如 i915、amdgpu、nvidia
+    pub driver: Option<String>,
+    /// PCI 厂商和设备 ID
+    pub pci_id: Option<(u16, u16)>,
+}
+
+impl RenderNode {
+    /// 列出系统中的 render node，按路径排序
+    pub fn scan() -> Vec<RenderNode> {
+        Self::scan_in(Path::new(DEV_DRI), Path::new(SYS_CLASS_DRM))
+    }
+
+    fn scan_in(dev_dri: &Path, sys_class_drm: &Path) -> Vec<RenderNode> {
+        let Ok(entries) = std::fs::read_dir(dev_dri) else {
+            return Vec::new();
+        };
+        let mut nodes: Vec<RenderNode> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("renderD"))
+            .map(|entry| {
+                let device = sys_class_drm.join(entry.file_name()).join("device");
+                RenderNode {
+                    path: entry.path(),
+                    driver: std::fs::read_link(device.join("driver"))
+                        .ok()
+                        .and_then(|link| Some(link.file_name()?.to_string_lossy().into_owned())),
+                    pci_id: read_hex(&device.join("vendor")).zip(read_hex(&device.join("device"))),
+                }
+            })
+            .collect();
+        nodes.sort_by(|a, b| a.path.cmp(&b.path));
+        nodes
+    }
+
+    pub fn path_str(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    pub fn is_nvidia(&self) -> bool {
+        self.driver.as_deref() == Some("nvidia")
+    }
+}
+
+impl std::fmt::Display for RenderNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(driver) = &self.driver {
+            write!(f, " ({}", driver)?;
+            if let Some((vendor, device)) = self.pci_id {
+                write!(f, ", {:04x}:{:04x}", vendor, device)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// 读取 sysfs 中 "0x8086" 形式的 ID
+fn read_hex(path: &Path) -> Option<u16> {
+    let content = std::fs::read_to_string(path).ok()?;
+    u16::from_str_radix(content.trim().trim_start_matches("0x"), 16).ok()
+}
+
+/// 选择用于解码的设备：路径（/dev/dri/renderD129 或 renderD129）、PCI ID（10de:25a0）或驱动名（amdgpu）
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum DeviceSelector {
+    Path(PathBuf),
+    PciId { vendor: u16, device: u16 },
+    Driver(String),
+}
+
+impl DeviceSelector {
+    pub fn matches(&self, node: &RenderNode) -> bool {
+        match self {
+            DeviceSelector::Path(path) => node.path == *path,
+            DeviceSelector::PciId { vendor, device } => node.pci_id == Some((*vendor, *device)),
+            DeviceSelector::Driver(driver) => node.driver.as_deref() == Some(driver.as_str()),
+        }
+    }
+
+    /// 系统中符合条件的 render node
+    pub fn resolve(&self) -> Vec<RenderNode> {
+        RenderNode::scan().into_iter().filter(|node| self.matches(node)).collect()
+    }
+}
+
+impl FromStr for DeviceSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("empty device selector".to_string());
+        }
+        if s.contains('/') {
+            return Ok(DeviceSelector::Path(PathBuf::from(s)));
+        }
+        if s.starts_with("renderD") {
+            return Ok(DeviceSelector::Path(Path::new(DEV_DRI).join(s)));
+        }
+        if let Some((vendor, device)) = s.split_once(':') {
+            let parse = |id: &str| if id.len() == 4 { u16::from_str_radix(id, 16).ok() } else { None };
+            return match (parse(vendor), parse(device)) {
+                (Some(vendor), Some(device)) => Ok(DeviceSelector::PciId { vendor, device }),
+                _ => Err(format!("invalid PCI ID: {} (expected vendor:device, e.g. 10de:25a0)", s)),
+            };
+        }
+        Ok(DeviceSelector::Driver(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for DeviceSelector {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<DeviceSelector> for String {
+    fn from(selector: DeviceSelector) -> Self {
+        selector.to_string()
+    }
+}
+
+impl std::fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceSelector::Path(path) => write!(f, "{}", path.display()),
+            DeviceSelector::PciId { vendor, device } => write!(f, "{:04x}:{:04x}", vendor, device),
+            DeviceSelector::Driver(driver) => write!(f, "{}", driver),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn node(path: &str, driver: &str, pci_id: (u16, u16)) -> RenderNode {
+        RenderNode { path: PathBuf::from(path), driver: Some(driver.to_string()), pci_id: Some(pci_id) }
+    }
+
+    #[test]
+    fn test_parse_selector() {
+        assert_eq!("/dev/dri/renderD129".parse(), Ok(DeviceSelector::Path(PathBuf::from("/dev/dri/renderD129"))));
+        assert_eq!("renderD128".parse(), Ok(DeviceSelector::Path(PathBuf::from("/dev/dri/renderD128"))));
+        assert_eq!("10DE:25a0".parse(), Ok(DeviceSelector::PciId { vendor: 0x10de, device: 0x25a0 }));
+        assert_eq!("amdgpu".parse(), Ok(DeviceSelector::Driver("amdgpu".to_string())));
+        assert!("10de:xyz".parse::<DeviceSelector>().is_err());
+        assert!("".parse::<DeviceSelector>().is_err());
+        assert_eq!(DeviceSelector::PciId { vendor: 0x8086, device: 0x46a6 }.to_string(), "8086:46a6");
+    }
+
+    #[test]
+    fn test_match_nodes() {
+        let igpu = node("/dev/dri/renderD128", "i915", (0x8086, 0x46a6));
+        let dgpu = node("/dev/dri/renderD129", "nvidia", (0x10de, 0x25a0));
+
+        let selector: DeviceSelector = "10de:25a0".parse().unwrap();
+        assert!(!selector.matches(&igpu));
+        assert!(selector.matches(&dgpu));
+
+        let selector: DeviceSelector = "i915".parse().unwrap();
+        assert!(selector.matches(&igpu));
+        assert!(!selector.matches(&dgpu));
+
+        let selector: DeviceSelector = "renderD129".parse().unwrap();
+        assert!(selector.matches(&dgpu));
+    }
+}
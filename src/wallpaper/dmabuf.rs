@@ -0,0 +1,110 @@
+use anyhow::Result;
+use ffmpeg_next as ffmpeg;
+
+use ffmpeg::util::frame::video::Video;
+
+use crate::wayland::{DmabufAttributes, DmabufPlane};
+
+// DRM fourcc，见 drm_fourcc.h
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    (code[0] as u32) | ((code[1] as u32) << 8) | ((code[2] as u32) << 16) | ((code[3] as u32) << 24)
+}
+
+pub const DRM_FORMAT_NV12: u32 = fourcc(b"NV12");
+pub const DRM_FORMAT_P010: u32 = fourcc(b"P010");
+const DRM_FORMAT_R8: u32 = fourcc(b"R8  ");
+const DRM_FORMAT_GR88: u32 = fourcc(b"GR88");
+const DRM_FORMAT_R16: u32 = fourcc(b"R16 ");
+const DRM_FORMAT_GR1616: u32 = fourcc(b"GR32");
+
+// libavutil/hwcontext.h 中的匿名枚举
+const AV_HWFRAME_MAP_READ: i32 = 1;
+
+/// 映射为 DRM PRIME 的硬件帧
+/// 持有期间解码器不会复用对应的硬件 surface，因此要保持到 compositor release 该 buffer
+pub struct DmabufFrame {
+    frame: *mut ffmpeg::ffi::AVFrame,
+    attributes: DmabufAttributes,
+}
+
+// AVFrame 只在释放时访问，可以跨线程移动和共享（多个输出共用一帧）
+unsafe impl Send for DmabufFrame {}
+unsafe impl Sync for DmabufFrame {}
+
+impl DmabufFrame {
+    /// 通过 av_hwframe_map 把硬件帧（VAAPI）导出为 DMA-BUF
+    pub fn export(hw_frame: &Video) -> Result<Self> {
+        unsafe {
+            let mut frame = ffmpeg::ffi::av_frame_alloc();
+            if frame.is_null() {
+                return Err(anyhow::anyhow!("Failed to allocate frame"));
+            }
+            (*frame).format = ffmpeg::ffi::AVPixelFormat::AV_PIX_FMT_DRM_PRIME as i32;
+
+            let ret = ffmpeg::ffi::av_hwframe_map(frame, hw_frame.as_ptr(), AV_HWFRAME_MAP_READ);
+            if ret < 0 {
+                ffmpeg::ffi::av_frame_free(&mut frame);
+                return Err(anyhow::anyhow!("Failed to map hardware frame to DRM PRIME: error code {}", ret));
+            }
+
+            let descriptor = &*((*frame).data[0] as *const ffmpeg::ffi::AVDRMFrameDescriptor);
+            match describe(descriptor, hw_frame.width(), hw_frame.height()) {
+                Ok(attributes) => Ok(Self { frame, attributes }),
+                Err(e) => {
+                    ffmpeg::ffi::av_frame_free(&mut frame);
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    pub fn attributes(&self) -> &DmabufAttributes {
+        &self.attributes
+    }
+}
+
+impl Drop for DmabufFrame {
+    fn drop(&mut self) {
+        unsafe {
+            ffmpeg::ffi::av_frame_free(&mut self.frame);
+        }
+    }
+}
+
+/// 把 AVDRMFrameDescriptor 转换为 linux-dmabuf 需要的格式和平面
+/// VAAPI 默认按分离的 layer 导出（NV12 为 R8 + GR88），这里合并为一个多平面格式
+fn describe(descriptor: &ffmpeg::ffi::AVDRMFrameDescriptor, width: u32, height: u32) -> Result<DmabufAttributes> {
+    let layers = &descriptor.layers[..descriptor.nb_layers as usize];
+    let layer_formats: Vec<u32> = layers.iter().map(|layer| layer.format).collect();
+    let format = match layer_formats.as_slice() {
+        [format] => *format,
+        [DRM_FORMAT_R8, DRM_FORMAT_GR88] => DRM_FORMAT_NV12,
+        [DRM_FORMAT_R16, DRM_FORMAT_GR1616] => DRM_FORMAT_P010,
+        _ => return Err(anyhow::anyhow!("Unsupported DRM layer layout: {:08x?}", layer_formats)),
+    };
+
+    let mut planes = Vec::new();
+    let mut modifier = None;
+    for layer in layers {
+        for plane in &layer.planes[..layer.nb_planes as usize] {
+            let object = &descriptor.objects[plane.object_index as usize];
+            // 所有平面的 modifier 必须一致
+            if *modifier.get_or_insert(object.format_modifier) != object.format_modifier {
+                return Err(anyhow::anyhow!("DRM planes use different modifiers"));
+            }
+            planes.push(DmabufPlane {
+                fd: object.fd,
+                offset: plane.offset as u32,
+                stride: plane.pitch as u32,
+            });
+        }
+    }
+
+    Ok(DmabufAttributes {
+        width,
+        height,
+        format,
+        modifier: modifier.ok_or_else(|| anyhow::anyhow!("DRM frame has no planes"))?,
+        planes,
+    })
+}
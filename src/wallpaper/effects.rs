@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+/// 壁纸的画面效果，在解码之后、转换为 SHM 像素格式之前由 FFmpeg filter 处理
+/// 亮度、对比度、饱和度和模糊先应用，`filter` 中的自定义 filtergraph 最后应用
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Effects {
+    /// 亮度 -1.0 - 1.0，默认 0，负值变暗
+    pub brightness: Option<f64>,
+    /// 对比度 0.0 - 3.0，默认 1
+    pub contrast: Option<f64>,
+    /// 饱和度 0.0 - 3.0，默认 1，0 为黑白
+    pub saturation: Option<f64>,
+    /// 高斯模糊的 sigma 0.0 - 100.0，0 表示不模糊
+    pub blur: Option<f64>,
+    /// 自定义的 FFmpeg filtergraph，例如 "hue=h=90,unsharp,hflip"，只能有一个输入和一个输出
+    pub filter: Option<String>,
+}
+
+impl Effects {
+    /// 检查参数范围
+    pub fn validate(&self) -> Result<(), String> {
+        let check = |name: &str, value: Option<f64>, min: f64, max: f64| match value {
+            Some(value) if !(min..=max).contains(&value) => {
+                Err(format!("{} must be between {} and {}, got {}", name, min, max, value))
+            }
+            _ => Ok(()),
+        };
+        check("brightness", self.brightness, -1.0, 1.0)?;
+        check("contrast", self.contrast, 0.0, 3.0)?;
+        check("saturation", self.saturation, 0.0, 3.0)?;
+        check("blur", self.blur, 0.0, 100.0)?;
+        if self.filter.as_deref().is_some_and(|filter| filter.contains(';')) {
+            return Err("filter must be a single chain without ';'".to_string());
+        }
+        Ok(())
+    }
+
+    /// 转换为 FFmpeg filtergraph，没有任何效果时返回 None
+    pub fn filtergraph(&self) -> Option<String> {
+        let mut chain = Vec::new();
+
+        let eq: Vec<String> = [("brightness", self.brightness), ("contrast", self.contrast), ("saturation", self.saturation)]
+            .into_iter()
+            .filter_map(|(name, value)| Some(format!("{}={}", name, value?)))
+            .collect();
+        if !eq.is_empty() {
+            chain.push(format!("eq={}", eq.join(":")));
+        }
+        if let Some(blur) = self.blur.filter(|&sigma| sigma > 0.0) {
+            chain.push(format!("gblur=sigma={}", blur));
+        }
+        if let Some(filter) = self.filter.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+            chain.push(filter.to_string());
+        }
+
+        (!chain.is_empty()).then(|| chain.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_filtergraph() {
+        assert_eq!(Effects::default().filtergraph(), None);
+
+        let effects = Effects {
+            brightness: Some(-0.2),
+            saturation: Some(0.0),
+            blur: Some(8.0),
+            filter: Some(" hflip ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(effects.filtergraph().as_deref(), Some("eq=brightness=-0.2:saturation=0,gblur=sigma=8,hflip"));
+
+        let effects = Effects { blur: Some(0.0), filter: Some(String::new()), ..Default::default() };
+        assert_eq!(effects.filtergraph(), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Effects { brightness: Some(-1.0), contrast: Some(2.5), ..Default::default() }.validate().is_ok());
+        assert!(Effects { brightness: Some(1.5), ..Default::default() }.validate().is_err());
+        assert!(Effects { blur: Some(-1.0), ..Default::default() }.validate().is_err());
+        assert!(Effects { filter: Some("split[a][b];[a][b]hstack".to_string()), ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+
+use ffmpeg_next as ffmpeg;
+
+use ffmpeg::filter::Graph;
+use ffmpeg::format::Pixel;
+use ffmpeg::util::frame::video::Video;
+
+use crate::wallpaper::hwaccel::HardwareAcceleration;
+use crate::wayland::ScalingFilter;
+
+/// GPU 缩放的输入输出参数，任意一项变化时重建 filter graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GraphParams {
+    /// 输入帧所属的 hw_frames_ctx，解码器重新分配 surface（例如分辨率变化）时改变
+    frames_ctx: *const u8,
+    src_width: u32,
+    src_height: u32,
+    dst_format: Pixel,
+    dst_width: u32,
+    dst_height: u32,
+}
+
+/// 在 GPU 上缩放和转换硬件帧（scale_vaapi / scale_cuda），再下载到内存
+/// 4K 源显示在 1080p 输出上时，只需要传输和转换缩小后的帧
+pub struct GpuScaler {
+    hw_accel_type: HardwareAcceleration,
+    filter: ScalingFilter,
+    graph: Option<(Graph, GraphParams)>,
+}
+
+impl GpuScaler {
+    /// 后端没有对应的缩放 filter，或者 FFmpeg 编译时没有包含它时返回 None
+    pub fn new(hw_accel_type: HardwareAcceleration, filter: ScalingFilter) -> Option<Self> {
+        ffmpeg::filter::find(scale_filter_name(hw_accel_type)?)?;
+        Some(Self { hw_accel_type, filter, graph: None })
+    }
+
+    /// 把硬件帧缩放到目标尺寸，下载为 `dst_format`（NV12 或 P010）的软件帧
+    /// NV12/P010 的尺寸必须是偶数，奇数尺寸向下取整
+    pub fn run(&mut self, input: &Video, dst_format: Pixel, dst_width: u32, dst_height: u32) -> Result<Video> {
+        let frames_ctx = unsafe { (*input.as_ptr()).hw_frames_ctx };
+        if frames_ctx.is_null() {
+            return Err(anyhow::anyhow!("Frame is not a hardware frame"));
+        }
+        let params = GraphParams {
+            frames_ctx: unsafe { (*frames_ctx).data } as *const u8,
+            src_width: input.width(),
+            src_height: input.height(),
+            dst_format,
+            dst_width: (dst_width & !1).max(2),
+            dst_height: (dst_height & !1).max(2),
+        };
+
+        let graph = match &mut self.graph {
+            Some((graph, current)) if *current == params => graph,
+            slot => {
+                let graph = build_graph(self.hw_accel_type, self.filter, input, frames_ctx, &params)?;
+                &mut slot.insert((graph, params)).0
+            }
+        };
+
+        filter_frame(graph, input)?.ok_or_else(|| anyhow::anyhow!("GPU scaler produced no frame"))
+    }
+}
+
+/// 软件帧的输入参数和 filtergraph，任意一项变化时重建
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FilterParams {
+    spec: String,
+    format: Pixel,
+    width: u32,
+    height: u32,
+}
+
+/// 用户设置的画面效果（FFmpeg filtergraph），作用于解码后的软件帧
+/// filtergraph 可以在播放中替换；无法使用时跳过效果，直到 filtergraph 再次改变
+pub struct VideoFilter {
+    spec: Option<String>,
+    graph: Option<(Graph, FilterParams)>,
+    failed: bool,
+}
+
+impl VideoFilter {
+    pub fn new(spec: Option<String>) -> Self {
+        Self { spec, graph: None, failed: false }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.spec.is_some() && !self.failed
+    }
+
+    /// 替换 filtergraph，下一帧开始生效
+    pub fn set_spec(&mut self, spec: Option<String>) {
+        if spec != self.spec {
+            info!("Video filter changed: {}", spec.as_deref().unwrap_or("none"));
+            self.spec = spec;
+            self.graph = None;
+            self.failed = false;
+        }
+    }
+
+    /// 对一帧应用效果；没有效果或效果无法使用时原样返回，filter 暂时没有输出时返回 None
+    pub fn run(&mut self, input: Video) -> Option<Video> {
+        let Some(spec) = self.spec.clone().filter(|_| !self.failed) else {
+            return Some(input);
+        };
+        match self.try_run(spec, &input) {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Video filter unavailable, showing frames without effects: {:#}", e);
+                self.failed = true;
+                self.graph = None;
+                Some(input)
+            }
+        }
+    }
+
+    fn try_run(&mut self, spec: String, input: &Video) -> Result<Option<Video>> {
+        let params = FilterParams {
+            spec,
+            format: input.format(),
+            width: input.width(),
+            height: input.height(),
+        };
+
+        let graph = match &mut self.graph {
+            Some((graph, current)) if *current == params => graph,
+            slot => {
+                debug!("Creating video filter: {} ({:?} {}x{})", params.spec, params.format, params.width, params.height);
+                let graph = new_graph(params.format, params.width, params.height, None, &params.spec)?;
+                &mut slot.insert((graph, params)).0
+            }
+        };
+        filter_frame(graph, input)
+    }
+}
+
+/// 检查 filtergraph 能否创建，用于在 IPC 请求中提前报告错误
+pub fn check_filtergraph(spec: &str) -> Result<()> {
+    ffmpeg::init().map_err(|e| anyhow::anyhow!("Failed to initialize ffmpeg: {}", e))?;
+    new_graph(Pixel::YUV420P, 64, 64, None, spec).map(|_| ())
+}
+
+/// 把一帧送入 filter graph，取出最新的输出帧；filter 需要更多输入时返回 None
+fn filter_frame(graph: &mut Graph, input: &Video) -> Result<Option<Video>> {
+    graph
+        .get("in")
+        .ok_or_else(|| anyhow::anyhow!("Filter graph has no input"))?
+        .source()
+        .add(input)
+        .map_err(|e| anyhow::anyhow!("Failed to send frame to filter graph: {}", e))?;
+
+    let mut sink = graph.get("out").ok_or_else(|| anyhow::anyhow!("Filter graph has no output"))?;
+    let mut latest = None;
+    loop {
+        let mut output = Video::empty();
+        match sink.sink().frame(&mut output) {
+            Ok(()) => latest = Some(output),
+            Err(ffmpeg::Error::Other { errno: libc::EAGAIN }) | Err(ffmpeg::Error::Eof) => return Ok(latest),
+            Err(e) => return Err(anyhow::anyhow!("Failed to receive frame from filter graph: {}", e)),
+        }
+    }
+}
+
+/// buffer -> `spec` -> buffersink；`frames_ctx` 为硬件帧所属的 hw_frames_ctx
+fn new_graph(
+    format: Pixel,
+    width: u32,
+    height: u32,
+    frames_ctx: Option<*mut ffmpeg::ffi::AVBufferRef>,
+    spec: &str,
+) -> Result<Graph> {
+    let buffer = ffmpeg::filter::find("buffer").ok_or_else(|| anyhow::anyhow!("Failed to find buffer filter"))?;
+    let buffersink =
+        ffmpeg::filter::find("buffersink").ok_or_else(|| anyhow::anyhow!("Failed to find buffersink filter"))?;
+
+    let mut graph = Graph::new();
+    let args = format!(
+        "video_size={}x{}:pix_fmt={}:time_base=1/1:pixel_aspect=1/1",
+        width,
+        height,
+        ffmpeg::ffi::AVPixelFormat::from(format) as i32
+    );
+    let mut source = graph
+        .add(&buffer, "in", &args)
+        .map_err(|e| anyhow::anyhow!("Failed to create buffer source: {}", e))?;
+    graph
+        .add(&buffersink, "out", "")
+        .map_err(|e| anyhow::anyhow!("Failed to create buffer sink: {}", e))?;
+
+    // 输入是硬件帧时，buffer source 需要知道它们所属的 hw_frames_ctx，GPU filter 据此找到设备
+    if let Some(frames_ctx) = frames_ctx {
+        unsafe {
+            let parameters = ffmpeg::ffi::av_buffersrc_parameters_alloc();
+            if parameters.is_null() {
+                return Err(anyhow::anyhow!("Failed to allocate buffer source parameters"));
+            }
+            (*parameters).hw_frames_ctx = frames_ctx;
+            let ret = ffmpeg::ffi::av_buffersrc_parameters_set(source.as_mut_ptr(), parameters);
+            ffmpeg::ffi::av_free(parameters as *mut _);
+            if ret < 0 {
+                return Err(anyhow::anyhow!("Failed to set hw_frames_ctx on buffer source: {}", ffmpeg::Error::from(ret)));
+            }
+        }
+    }
+
+    graph
+        .output("in", 0)
+        .and_then(|parser| parser.input("out", 0))
+        .and_then(|parser| parser.parse(spec))
+        .map_err(|e| anyhow::anyhow!("Failed to parse filter graph '{}': {}", spec, e))?;
+    graph
+        .validate()
+        .map_err(|e| anyhow::anyhow!("Failed to configure filter graph '{}': {}", spec, e))?;
+    Ok(graph)
+}
+
+/// 后端对应的 GPU 缩放 filter
+fn scale_filter_name(hw_accel_type: HardwareAcceleration) -> Option<&'static str> {
+    match hw_accel_type {
+        HardwareAcceleration::VAAPI => Some("scale_vaapi"),
+        HardwareAcceleration::CUDA => Some("scale_cuda"),
+        _ => None,
+    }
+}
+
+/// scale_vaapi/scale_cuda -> hwdownload -> format
+fn build_graph(
+    hw_accel_type: HardwareAcceleration,
+    filter: ScalingFilter,
+    input: &Video,
+    frames_ctx: *mut ffmpeg::ffi::AVBufferRef,
+    params: &GraphParams,
+) -> Result<Graph> {
+    let scale_filter = scale_filter_name(hw_accel_type)
+        .ok_or_else(|| anyhow::anyhow!("No GPU scaling filter for {}", hw_accel_type))?;
+    let format_name = match params.dst_format {
+        Pixel::P010LE => "p010le",
+        _ => "nv12",
+    };
+    let quality = match hw_accel_type {
+        HardwareAcceleration::CUDA => format!("interp_algo={}", cuda_interp_algo(filter)),
+        _ => format!("mode={}", vaapi_mode(filter)),
+    };
+    let spec = format!(
+        "{}=w={}:h={}:format={}:{},hwdownload,format={}",
+        scale_filter, params.dst_width, params.dst_height, format_name, quality, format_name
+    );
+    debug!(
+        "Creating GPU scaler: {}x{} -> {}x{} ({})",
+        params.src_width, params.src_height, params.dst_width, params.dst_height, spec
+    );
+
+    new_graph(input.format(), input.width(), input.height(), Some(frames_ctx), &spec)
+}
+
+fn vaapi_mode(filter: ScalingFilter) -> &'static str {
+    match filter {
+        ScalingFilter::FastBilinear => "fast",
+        ScalingFilter::Bilinear => "default",
+        ScalingFilter::Bicubic | ScalingFilter::Lanczos => "hq",
+    }
+}
+
+fn cuda_interp_algo(filter: ScalingFilter) -> &'static str {
+    match filter {
+        ScalingFilter::FastBilinear | ScalingFilter::Bilinear => "bilinear",
+        ScalingFilter::Bicubic => "bicubic",
+        ScalingFilter::Lanczos => "lanczos",
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+/// 按时间戳选帧，把源帧率均匀地降到目标帧率
+/// 输出时刻排成间隔为 1/target_fps 的网格，每个网格时刻取第一个到达的帧，
+/// 因此 60→45、50→30 这类非整数比例也能得到均匀的输出；源帧率低于目标时保留所有帧
+#[derive(Debug, Clone)]
+pub struct FrameRateConverter {
+    interval: Duration,
+    /// 下一个输出时刻，None 表示下一帧直接输出
+    next: Option<Duration>,
+}
+
+impl FrameRateConverter {
+    /// 时间戳取整误差的容差
+    const TOLERANCE: Duration = Duration::from_millis(1);
+    /// 时间戳向后跳超过这个时长（循环、seek）时重新开始
+    const RESET_THRESHOLD: Duration = Duration::from_secs(1);
+
+    pub fn new(target_fps: u32) -> Self {
+        Self { interval: Duration::from_secs_f64(1.0 / target_fps.max(1) as f64), next: None }
+    }
+
+    /// 是否输出时间戳为 `pts` 的帧，输出时推进到下一个网格时刻
+    pub fn accept(&mut self, pts: Duration) -> bool {
+        if !self.would_accept(pts) {
+            return false;
+        }
+        self.next = Some(match self.next {
+            // 从网格时刻而不是帧时间推进，保持输出间隔均匀
+            Some(next) if pts + Self::TOLERANCE >= next && pts < next + self.interval => next + self.interval,
+            // 第一帧或时间戳跳变，从这一帧重新对齐
+            _ => pts + self.interval,
+        });
+        true
+    }
+
+    /// 不改变状态，判断这一帧是否会被输出；为 false 时这一帧一定会被丢弃，可以跳过解码
+    pub fn would_accept(&self, pts: Duration) -> bool {
+        match self.next {
+            None => true,
+            Some(next) => pts + Self::TOLERANCE >= next || pts + Self::RESET_THRESHOLD < next,
+        }
+    }
+
+    /// 视频循环或 seek 后调用，下一帧直接输出
+    pub fn reset(&mut self) {
+        self.next = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// 按源帧率生成一秒的时间戳，返回输出的帧数和被输出的帧序号
+    fn convert(source_fps: u32, target_fps: u32) -> Vec<u32> {
+        let mut converter = FrameRateConverter::new(target_fps);
+        (0..source_fps)
+            .filter(|&i| converter.accept(Duration::from_secs_f64(i as f64 / source_fps as f64)))
+            .collect()
+    }
+
+    #[test]
+    fn test_non_integer_ratios() {
+        // 60→45：每 4 帧取 3 帧
+        let frames = convert(60, 45);
+        assert_eq!(frames.len(), 45);
+        assert_eq!(&frames[..6], &[0, 2, 3, 4, 6, 7]);
+
+        // 50→30：均匀取帧，间隔只有 1 或 2 帧
+        let frames = convert(50, 30);
+        assert_eq!(frames.len(), 30);
+        assert!(frames.windows(2).all(|w| (1..=2).contains(&(w[1] - w[0]))));
+
+        assert_eq!(convert(60, 30).len(), 30);
+        assert_eq!(convert(120, 60).len(), 60);
+    }
+
+    #[test]
+    fn test_slower_source_keeps_all_frames() {
+        assert_eq!(convert(24, 30).len(), 24);
+        assert_eq!(convert(30, 30).len(), 30);
+    }
+
+    #[test]
+    fn test_reset_on_backward_jump() {
+        let mut converter = FrameRateConverter::new(30);
+        assert!(converter.accept(Duration::from_secs(10)));
+        assert!(!converter.would_accept(Duration::from_millis(10_010)));
+        // 视频循环回开头
+        assert!(converter.accept(Duration::ZERO));
+        assert!(!converter.accept(Duration::from_millis(10)));
+        assert!(converter.accept(Duration::from_millis(34)));
+    }
+}
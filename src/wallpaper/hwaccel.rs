@@ -0,0 +1,633 @@
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use ffmpeg_next as ffmpeg;
+
+use ffmpeg::codec::{Codec, Id};
+use ffmpeg::ffi::AVHWDeviceType;
+use ffmpeg::format::Pixel;
+use ffmpeg::format::context::Input;
+use ffmpeg::util::frame::video::Video;
+
+use crate::wallpaper::devices::{DeviceSelector, RenderNode};
+
+/// DMA-BUF 模式下解码器额外分配的硬件 surface 数量
+const DMABUF_EXTRA_HW_FRAMES: i32 = 16;
+
+/// 解码后端，只包含 Wayland 桌面上可用的 Linux 后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum HardwareAcceleration {
+    /// 依次探测每个 render node 上的 VAAPI、CUDA、VDPAU，都不可用时软件解码
+    #[default]
+    Auto,
+    /// VAAPI (Video Acceleration API) - Intel/AMD GPU
+    VAAPI,
+    /// CUDA/NVDEC - NVIDIA GPU
+    CUDA,
+    /// VDPAU (Video Decode and Presentation API for Unix) - NVIDIA GPU
+    VDPAU,
+    /// QSV (Quick Sync Video) - Intel GPU，使用 FFmpeg 的 *_qsv 解码器
+    QSV,
+    /// Vulkan Video - 需要驱动支持 Vulkan 视频解码扩展
+    Vulkan,
+    /// 软件解码
+    Software,
+}
+
+impl HardwareAcceleration {
+    /// 对应的 FFmpeg 硬件设备类型，Auto 和 Software 没有
+    pub fn device_type(&self) -> Option<AVHWDeviceType> {
+        match self {
+            HardwareAcceleration::VAAPI => Some(AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI),
+            HardwareAcceleration::CUDA => Some(AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA),
+            HardwareAcceleration::VDPAU => Some(AVHWDeviceType::AV_HWDEVICE_TYPE_VDPAU),
+            HardwareAcceleration::QSV => Some(AVHWDeviceType::AV_HWDEVICE_TYPE_QSV),
+            HardwareAcceleration::Vulkan => Some(AVHWDeviceType::AV_HWDEVICE_TYPE_VULKAN),
+            HardwareAcceleration::Auto | HardwareAcceleration::Software => None,
+        }
+    }
+
+    /// 解码输出的硬件像素格式，Auto 和 Software 没有
+    pub fn hw_pixel_format(&self) -> Option<Pixel> {
+        match self {
+            HardwareAcceleration::VAAPI => Some(Pixel::VAAPI),
+            HardwareAcceleration::CUDA => Some(Pixel::CUDA),
+            HardwareAcceleration::VDPAU => Some(Pixel::VDPAU),
+            HardwareAcceleration::QSV => Some(Pixel::QSV),
+            HardwareAcceleration::Vulkan => Some(Pixel::VULKAN),
+            HardwareAcceleration::Auto | HardwareAcceleration::Software => None,
+        }
+    }
+
+    /// 是否是具体的硬件后端
+    pub fn is_hardware(&self) -> bool {
+        self.device_type().is_some()
+    }
+}
+
+impl std::fmt::Display for HardwareAcceleration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            HardwareAcceleration::Auto => "auto",
+            HardwareAcceleration::VAAPI => "vaapi",
+            HardwareAcceleration::CUDA => "cuda",
+            HardwareAcceleration::VDPAU => "vdpau",
+            HardwareAcceleration::QSV => "qsv",
+            HardwareAcceleration::Vulkan => "vulkan",
+            HardwareAcceleration::Software => "software",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for HardwareAcceleration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(HardwareAcceleration::Auto),
+            "vaapi" => Ok(HardwareAcceleration::VAAPI),
+            "cuda" | "nvdec" => Ok(HardwareAcceleration::CUDA),
+            "vdpau" => Ok(HardwareAcceleration::VDPAU),
+            "qsv" => Ok(HardwareAcceleration::QSV),
+            "vulkan" => Ok(HardwareAcceleration::Vulkan),
+            "software" | "none" | "off" => Ok(HardwareAcceleration::Software),
+            _ => Err(format!(
+                "unknown hardware acceleration: {} (expected auto, vaapi, cuda, vdpau, qsv, vulkan or software)",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for HardwareAcceleration {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<HardwareAcceleration> for String {
+    fn from(hw_accel_type: HardwareAcceleration) -> Self {
+        hw_accel_type.to_string()
+    }
+}
+
+/// 硬件解码相关的错误
+#[derive(Debug, thiserror::Error)]
+pub enum HwAccelError {
+    #[error("{0} is not a hardware backend")]
+    NotHardware(HardwareAcceleration),
+    #[error("no decoder for codec {0:?}")]
+    NoDecoder(Id),
+    #[error("{codec} cannot be decoded with {backend}")]
+    CodecUnsupported { backend: HardwareAcceleration, codec: String },
+    #[error("no render node matches device {0}")]
+    NoMatchingDevice(DeviceSelector),
+    #[error("failed to open {backend} device {}: {source}", .device.as_deref().unwrap_or("(default)"))]
+    DeviceUnavailable { backend: HardwareAcceleration, device: Option<String>, source: ffmpeg::Error },
+    #[error("failed to open {codec} decoder with {backend}: {source}")]
+    OpenDecoder { backend: HardwareAcceleration, codec: String, source: ffmpeg::Error },
+    #[error("{backend} fell back to software decoding ({format:?} frames)")]
+    SoftwareFallback { backend: HardwareAcceleration, format: Pixel },
+    #[error("failed to decode with {backend}: {source}")]
+    Decode { backend: HardwareAcceleration, source: ffmpeg::Error },
+    #[error("failed to transfer {backend} frame to system memory: {source}")]
+    Transfer { backend: HardwareAcceleration, source: ffmpeg::Error },
+}
+
+/// 硬件解码器包装器，软件解码时不持有设备
+pub struct HardwareDecoder {
+    hw_device_ctx: Option<*mut ffmpeg::ffi::AVBufferRef>,
+    hw_accel_type: HardwareAcceleration,
+    /// 打开的设备，例如 VAAPI 的 render node
+    device: Option<String>,
+}
+
+unsafe impl Send for HardwareDecoder {}
+unsafe impl Sync for HardwareDecoder {}
+
+impl HardwareDecoder {
+    /// 软件解码
+    pub fn software() -> Self {
+        Self {
+            hw_device_ctx: None,
+            hw_accel_type: HardwareAcceleration::Software,
+            device: None,
+        }
+    }
+
+    /// 打开硬件设备，`device` 为空时使用该后端的默认设备
+    /// QSV 的 `device` 是它底层使用的 VAAPI render node
+    pub fn new(hw_accel_type: HardwareAcceleration, device: Option<&str>) -> Result<Self, HwAccelError> {
+        let Some(hw_device_type) = hw_accel_type.device_type() else {
+            return Err(HwAccelError::NotHardware(hw_accel_type));
+        };
+        let unavailable = |source| HwAccelError::DeviceUnavailable {
+            backend: hw_accel_type,
+            device: device.map(str::to_string),
+            source,
+        };
+        info!("Initializing hardware device: {} ({})", hw_accel_type, device.unwrap_or("default"));
+
+        // 将 device 转换为 CString 以确保正确的 null 终止
+        let device_cstr = device
+            .map(std::ffi::CString::new)
+            .transpose()
+            .map_err(|_| unavailable(ffmpeg::Error::InvalidData))?;
+
+        let mut options: *mut ffmpeg::ffi::AVDictionary = std::ptr::null_mut();
+        let mut device_ptr = device_cstr.as_ref().map_or(std::ptr::null(), |d| d.as_ptr());
+        if hw_accel_type == HardwareAcceleration::QSV {
+            if let Some(device_cstr) = &device_cstr {
+                unsafe { ffmpeg::ffi::av_dict_set(&mut options, c"child_device".as_ptr(), device_cstr.as_ptr(), 0) };
+            }
+            device_ptr = std::ptr::null();
+        }
+
+        let mut hw_device_ctx_ptr: *mut ffmpeg::ffi::AVBufferRef = std::ptr::null_mut();
+        let ret = unsafe {
+            ffmpeg::ffi::av_hwdevice_ctx_create(&mut hw_device_ctx_ptr, hw_device_type, device_ptr, options, 0)
+        };
+        unsafe { ffmpeg::ffi::av_dict_free(&mut options) };
+
+        if ret < 0 {
+            return Err(unavailable(ffmpeg::Error::from(ret)));
+        }
+        if hw_device_ctx_ptr.is_null() {
+            return Err(unavailable(ffmpeg::Error::Bug));
+        }
+
+        info!("Hardware device context created successfully");
+
+        Ok(Self {
+            hw_device_ctx: Some(hw_device_ctx_ptr),
+            hw_accel_type,
+            device: device.map(str::to_string),
+        })
+    }
+
+    /// 实际使用的后端
+    pub fn hw_accel_type(&self) -> HardwareAcceleration {
+        self.hw_accel_type
+    }
+
+    /// 是否是这个后端输出的硬件帧，需要先传输到内存
+    pub fn is_hw_frame(&self, frame: &Video) -> bool {
+        self.hw_accel_type.hw_pixel_format() == Some(frame.format())
+    }
+
+    /// 配置解码器上下文使用硬件加速，必须在打开解码器之前调用
+    pub fn configure_context(&self, context: &mut ffmpeg::codec::context::Context) -> Result<(), HwAccelError> {
+        let Some(hw_device_ctx) = self.hw_device_ctx else {
+            return Ok(());
+        };
+
+        unsafe {
+            let codec_ctx = context.as_mut_ptr();
+
+            // 设置硬件设备上下文
+            (*codec_ctx).hw_device_ctx = ffmpeg::ffi::av_buffer_ref(hw_device_ctx);
+
+            // DMA-BUF 零拷贝时，帧通道和 compositor 会持有一部分硬件 surface，需要额外的 surface
+            if self.hw_accel_type == HardwareAcceleration::VAAPI {
+                (*codec_ctx).extra_hw_frames = DMABUF_EXTRA_HW_FRAMES;
+            }
+
+            if (*codec_ctx).hw_device_ctx.is_null() {
+                return Err(HwAccelError::DeviceUnavailable {
+                    backend: self.hw_accel_type,
+                    device: self.device.clone(),
+                    source: ffmpeg::Error::Other { errno: libc::ENOMEM },
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 从硬件帧传输到软件帧
+    pub fn transfer_frame(&self, hw_frame: &Video, sw_frame: &mut Video) -> Result<(), HwAccelError> {
+        if self.hw_device_ctx.is_none() {
+            return Err(HwAccelError::NotHardware(self.hw_accel_type));
+        }
+        let transfer_error = |ret: i32| HwAccelError::Transfer { backend: self.hw_accel_type, source: ffmpeg::Error::from(ret) };
+
+        unsafe {
+            let hw_frame_ptr = hw_frame.as_ptr();
+            let sw_frame_ptr = sw_frame.as_mut_ptr();
+
+            let ret = ffmpeg::ffi::av_hwframe_transfer_data(sw_frame_ptr, hw_frame_ptr, 0);
+            if ret < 0 {
+                return Err(transfer_error(ret));
+            }
+
+            // 复制帧属性（时间戳等）
+            let ret = ffmpeg::ffi::av_frame_copy_props(sw_frame_ptr, hw_frame_ptr);
+            if ret < 0 {
+                return Err(transfer_error(ret));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for HardwareDecoder {
+    fn drop(&mut self) {
+        if let Some(mut hw_device_ctx) = self.hw_device_ctx {
+            unsafe {
+                ffmpeg::ffi::av_buffer_unref(&mut hw_device_ctx);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for HardwareDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.device {
+            Some(device) => write!(f, "{} ({})", self.hw_accel_type, device),
+            None => write!(f, "{}", self.hw_accel_type),
+        }
+    }
+}
+
+/// 按偏好列出要尝试的后端和设备，软件解码总是最后一个
+/// 指定了设备时 VAAPI/QSV 只使用匹配的 render node，CUDA/VDPAU 只在匹配到 NVIDIA 设备时尝试
+fn candidates(
+    preference: HardwareAcceleration,
+    device: Option<&DeviceSelector>,
+) -> Vec<(HardwareAcceleration, Option<String>)> {
+    let nodes = match device {
+        Some(selector) => {
+            let nodes = selector.resolve();
+            if nodes.is_empty() {
+                warn!("{}", HwAccelError::NoMatchingDevice(selector.clone()));
+            }
+            nodes
+        }
+        None => RenderNode::scan(),
+    };
+    candidates_on(preference, device, &nodes)
+}
+
+/// 在已经找到的 render node 上列出候选，`nodes` 是符合 `device` 的节点（未指定设备时为全部节点）
+fn candidates_on(
+    preference: HardwareAcceleration,
+    device: Option<&DeviceSelector>,
+    nodes: &[RenderNode],
+) -> Vec<(HardwareAcceleration, Option<String>)> {
+    let nvidia = device.is_none() || nodes.iter().any(RenderNode::is_nvidia);
+
+    // 在每个 render node 上尝试；没有 render node 信息时使用默认设备
+    let per_node = |hw_accel_type| -> Vec<(HardwareAcceleration, Option<String>)> {
+        if nodes.is_empty() && device.is_none() {
+            return vec![(hw_accel_type, None)];
+        }
+        nodes.iter().map(|node| (hw_accel_type, Some(node.path_str()))).collect()
+    };
+
+    let mut candidates = match preference {
+        HardwareAcceleration::Auto => {
+            let mut list = per_node(HardwareAcceleration::VAAPI);
+            if nvidia {
+                list.push((HardwareAcceleration::CUDA, None));
+                list.push((HardwareAcceleration::VDPAU, None));
+            }
+            list
+        }
+        HardwareAcceleration::VAAPI | HardwareAcceleration::QSV => per_node(preference),
+        HardwareAcceleration::CUDA | HardwareAcceleration::VDPAU if !nvidia => Vec::new(),
+        HardwareAcceleration::Software => Vec::new(),
+        other => vec![(other, None)],
+    };
+    candidates.push((HardwareAcceleration::Software, None));
+    candidates
+}
+
+/// 使用该后端时的解码器：QSV 使用单独的 *_qsv 解码器，其他后端通过原生解码器的 hwaccel
+fn decoder_for(codec: Codec, hw_accel_type: HardwareAcceleration) -> Result<Codec, HwAccelError> {
+    let unsupported = || HwAccelError::CodecUnsupported { backend: hw_accel_type, codec: codec.name().to_string() };
+    let Some(device_type) = hw_accel_type.device_type() else {
+        return Ok(codec);
+    };
+
+    let decoder = if hw_accel_type == HardwareAcceleration::QSV {
+        let name = match codec.id() {
+            Id::MPEG2VIDEO => "mpeg2".to_string(),
+            _ => codec.name().to_string(),
+        };
+        ffmpeg::codec::decoder::find_by_name(&format!("{}_qsv", name)).ok_or_else(unsupported)?
+    } else {
+        codec
+    };
+
+    // 解码器是否支持通过设备上下文使用该后端
+    let mut index = 0;
+    loop {
+        let config = unsafe { ffmpeg::ffi::avcodec_get_hw_config(decoder.as_ptr(), index) };
+        if config.is_null() {
+            return Err(unsupported());
+        }
+        let (config_type, methods) = unsafe { ((*config).device_type, (*config).methods) };
+        if config_type == device_type
+            && methods & ffmpeg::ffi::AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX as i32 != 0
+        {
+            return Ok(decoder);
+        }
+        index += 1;
+    }
+}
+
+/// 用指定后端打开解码器
+fn open_with(
+    parameters: &ffmpeg::codec::Parameters,
+    codec: Codec,
+    hw_accel_type: HardwareAcceleration,
+    device: Option<&str>,
+) -> Result<(ffmpeg::codec::decoder::Video, HardwareDecoder), HwAccelError> {
+    let decoder_codec = decoder_for(codec, hw_accel_type)?;
+    let hw_decoder = if hw_accel_type.is_hardware() {
+        HardwareDecoder::new(hw_accel_type, device)?
+    } else {
+        HardwareDecoder::software()
+    };
+    let open_error = |source| HwAccelError::OpenDecoder {
+        backend: hw_accel_type,
+        codec: decoder_codec.name().to_string(),
+        source,
+    };
+
+    let mut context = ffmpeg::codec::context::Context::from_parameters(parameters.clone()).map_err(open_error)?;
+    hw_decoder.configure_context(&mut context)?;
+    let decoder = context
+        .decoder()
+        .open_as(decoder_codec)
+        .and_then(|opened| opened.video())
+        .map_err(open_error)?;
+    Ok((decoder, hw_decoder))
+}
+
+/// 确认后端时最多读取的包数，读完仍没有解码出帧时不再确认
+const CONFIRM_PACKETS: usize = 120;
+
+/// 解码第一帧，确认确实在使用硬件后端；完成后回到视频开头
+/// 设备不支持该视频的 profile 时解码器仍能打开，但 FFmpeg 会静默地用软件解码
+fn confirm_hardware(
+    ictx: &mut Input,
+    stream_index: usize,
+    decoder: &mut ffmpeg::codec::decoder::Video,
+    hw_decoder: &HardwareDecoder,
+) -> Result<(), HwAccelError> {
+    let backend = hw_decoder.hw_accel_type();
+    let mut frame = Video::empty();
+    let mut result = None;
+    for (stream, packet) in ictx.packets().take(CONFIRM_PACKETS) {
+        if stream.index() != stream_index {
+            continue;
+        }
+        if let Err(source) = decoder.send_packet(&packet) {
+            result = Some(Err(HwAccelError::Decode { backend, source }));
+            break;
+        }
+        if decoder.receive_frame(&mut frame).is_ok() {
+            result = Some(if hw_decoder.is_hw_frame(&frame) {
+                Ok(())
+            } else {
+                Err(HwAccelError::SoftwareFallback { backend, format: frame.format() })
+            });
+            break;
+        }
+    }
+
+    // 读过的包由解码循环重新读取
+    let _ = ictx.seek(0, ..);
+    decoder.flush();
+    result.unwrap_or_else(|| {
+        warn!("No frame decoded in the first {} packets, assuming {} works", CONFIRM_PACKETS, backend);
+        Ok(())
+    })
+}
+
+/// 按偏好依次尝试硬件加速（可限定设备），返回第一个能解码该视频流的解码器；都失败时使用软件解码
+/// 硬件后端需要解码出第一帧确认，因此会读取 `ictx` 开头的一些包，返回前回到视频开头
+pub fn open_video_decoder(
+    ictx: &mut Input,
+    stream_index: usize,
+    preference: HardwareAcceleration,
+    device: Option<&DeviceSelector>,
+) -> Result<(ffmpeg::codec::decoder::Video, HardwareDecoder), HwAccelError> {
+    let parameters = ictx
+        .stream(stream_index)
+        .map(|stream| stream.parameters())
+        .ok_or(HwAccelError::NoDecoder(Id::None))?;
+    let codec = ffmpeg::codec::decoder::find(parameters.id()).ok_or(HwAccelError::NoDecoder(parameters.id()))?;
+
+    let mut last_error = None;
+    for (hw_accel_type, device) in candidates(preference, device) {
+        let opened = open_with(&parameters, codec, hw_accel_type, device.as_deref()).and_then(|(mut decoder, hw_decoder)| {
+            if hw_accel_type.is_hardware() {
+                confirm_hardware(&mut *ictx, stream_index, &mut decoder, &hw_decoder)?;
+            }
+            Ok((decoder, hw_decoder))
+        });
+        match opened {
+            Ok((decoder, hw_decoder)) => {
+                info!("Decoding {} with {}", codec.name(), hw_decoder);
+                return Ok((decoder, hw_decoder));
+            }
+            Err(e) => {
+                warn!("{} unavailable: {}", hw_accel_type, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or(HwAccelError::NoDecoder(parameters.id())))
+}
+
+/// `devices` 命令检查的编码格式
+const PROBE_CODECS: &[Id] = &[Id::H264, Id::HEVC, Id::VP8, Id::VP9, Id::AV1, Id::MPEG2VIDEO, Id::VC1];
+
+/// 一个后端的探测结果：能解码的格式，或者设备无法打开的原因
+pub type BackendProbe = (HardwareAcceleration, Result<Vec<String>, HwAccelError>);
+
+/// 列出所有 render node 和其上可用的后端
+pub fn probe_devices() -> Result<Vec<(RenderNode, Vec<BackendProbe>)>> {
+    ffmpeg::init().map_err(|e| anyhow::anyhow!("Failed to initialize ffmpeg: {}", e))?;
+    Ok(RenderNode::scan()
+        .into_iter()
+        .map(|node| {
+            let backends = probe_device(&node);
+            (node, backends)
+        })
+        .collect())
+}
+
+/// 按驱动推测 render node 上可能可用的后端，逐个打开设备确认
+/// 格式来自 FFmpeg 解码器对该后端的支持，具体 profile 是否可用取决于驱动
+fn probe_device(node: &RenderNode) -> Vec<BackendProbe> {
+    let path = node.path_str();
+    let mut backends = vec![(HardwareAcceleration::VAAPI, Some(path.as_str()))];
+    match node.driver.as_deref() {
+        Some("i915") | Some("xe") => backends.push((HardwareAcceleration::QSV, Some(path.as_str()))),
+        Some("nvidia") => {
+            backends.push((HardwareAcceleration::CUDA, None));
+            backends.push((HardwareAcceleration::VDPAU, None));
+        }
+        _ => {}
+    }
+    backends.push((HardwareAcceleration::Vulkan, None));
+
+    backends
+        .into_iter()
+        .map(|(hw_accel_type, device)| {
+            let codecs = HardwareDecoder::new(hw_accel_type, device).map(|_| {
+                PROBE_CODECS
+                    .iter()
+                    .filter_map(|&id| ffmpeg::codec::decoder::find(id))
+                    .filter(|&codec| decoder_for(codec, hw_accel_type).is_ok())
+                    .map(|codec| codec.name().to_string())
+                    .collect()
+            });
+            (hw_accel_type, codecs)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn node(path: &str, driver: &str) -> RenderNode {
+        RenderNode { path: PathBuf::from(path), driver: Some(driver.to_string()), pci_id: None }
+    }
+
+    #[test]
+    fn test_auto_without_nodes() {
+        assert_eq!(
+            candidates_on(HardwareAcceleration::Auto, None, &[]),
+            vec![
+                (HardwareAcceleration::VAAPI, None),
+                (HardwareAcceleration::CUDA, None),
+                (HardwareAcceleration::VDPAU, None),
+                (HardwareAcceleration::Software, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nvidia_backends_need_nvidia_device() {
+        let amd: DeviceSelector = "amdgpu".parse().unwrap();
+        let nodes = [node("/dev/dri/renderD128", "amdgpu")];
+        assert_eq!(
+            candidates_on(HardwareAcceleration::CUDA, Some(&amd), &nodes),
+            vec![(HardwareAcceleration::Software, None)]
+        );
+        // 指定的设备不存在时不回退到默认设备
+        assert_eq!(
+            candidates_on(HardwareAcceleration::VDPAU, Some(&amd), &[]),
+            vec![(HardwareAcceleration::Software, None)]
+        );
+
+        let nvidia: DeviceSelector = "nvidia".parse().unwrap();
+        let nodes = [node("/dev/dri/renderD129", "nvidia")];
+        assert_eq!(
+            candidates_on(HardwareAcceleration::CUDA, Some(&nvidia), &nodes),
+            vec![(HardwareAcceleration::CUDA, None), (HardwareAcceleration::Software, None)]
+        );
+    }
+
+    #[test]
+    fn test_software_is_last() {
+        let nodes = [node("/dev/dri/renderD128", "i915"), node("/dev/dri/renderD129", "nvidia")];
+        for preference in [
+            HardwareAcceleration::Auto,
+            HardwareAcceleration::VAAPI,
+            HardwareAcceleration::CUDA,
+            HardwareAcceleration::VDPAU,
+            HardwareAcceleration::QSV,
+            HardwareAcceleration::Vulkan,
+            HardwareAcceleration::Software,
+        ] {
+            for nodes in [&nodes[..], &[]] {
+                let list = candidates_on(preference, None, nodes);
+                assert_eq!(list.last(), Some(&(HardwareAcceleration::Software, None)), "{}", preference);
+                assert_eq!(list.iter().filter(|(t, _)| *t == HardwareAcceleration::Software).count(), 1);
+            }
+        }
+
+        assert_eq!(
+            candidates_on(HardwareAcceleration::VAAPI, None, &nodes),
+            vec![
+                (HardwareAcceleration::VAAPI, Some("/dev/dri/renderD128".to_string())),
+                (HardwareAcceleration::VAAPI, Some("/dev/dri/renderD129".to_string())),
+                (HardwareAcceleration::Software, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_hwaccel() {
+        for hw_accel_type in [
+            HardwareAcceleration::Auto,
+            HardwareAcceleration::VAAPI,
+            HardwareAcceleration::CUDA,
+            HardwareAcceleration::VDPAU,
+            HardwareAcceleration::QSV,
+            HardwareAcceleration::Vulkan,
+            HardwareAcceleration::Software,
+        ] {
+            assert_eq!(hw_accel_type.to_string().parse::<HardwareAcceleration>(), Ok(hw_accel_type));
+        }
+        assert_eq!("NVDEC".parse(), Ok(HardwareAcceleration::CUDA));
+        assert_eq!("off".parse(), Ok(HardwareAcceleration::Software));
+        assert!("opencl".parse::<HardwareAcceleration>().is_err());
+        assert!("".parse::<HardwareAcceleration>().is_err());
+    }
+}
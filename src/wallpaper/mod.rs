@@ -1,9 +1,22 @@
+use crate::wallpaper::effects::Effects;
 use crate::wallpaper::project::Project;
+use crate::wayland::ConnectionState;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
+pub mod audio;
+pub mod clock;
+pub mod frame_rate;
+pub mod scaler;
 pub mod web;
 pub mod video;
 pub mod video_hw;
+pub mod hwaccel;
+pub mod filter;
+pub mod effects;
+pub mod devices;
+pub mod dmabuf;
+pub mod decoder_pool;
 pub mod project;
 pub mod player;
 
@@ -17,7 +30,22 @@ pub trait Wallpaper: Send + Sync {
     fn play(&mut self);
     fn pause(&mut self);
     fn run(&mut self);
+    fn stop(&mut self);
     fn info(&self);
+    /// 设置画面效果，正在播放时立即生效
+    fn set_effects(&mut self, effects: Effects) -> Result<()>;
+    fn status(&self) -> WallpaperStatus;
+}
+
+/// 单个壁纸的运行状态，用于 IPC 状态查询
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WallpaperStatus {
+    pub path: String,
+    pub output: String,
+    pub connection: ConnectionState,
+    /// 实际使用的解码后端，例如 "vaapi (/dev/dri/renderD128)" 或 "software"
+    #[serde(default)]
+    pub decoder: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -1,13 +1,21 @@
 use anyhow::Result;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::wallpaper::Wallpaper;
+use crate::wallpaper::audio::AudioControl;
+use crate::wallpaper::decoder_pool::DecoderPool;
+use crate::wallpaper::effects::Effects;
+use crate::wallpaper::{Wallpaper, WallpaperStatus};
+use crate::wayland::{ClaimedOutputs, OutputTarget};
 
 /// 壁纸播放器
-/// 保存和管理实现了 Wallpaper trait 的对象
+/// 保存和管理实现了 Wallpaper trait 的对象，每个输出目标一个壁纸
 pub struct Player {
-    wallpaper: Option<Box<dyn Wallpaper + Send>>,
+    wallpapers: HashMap<OutputTarget, Box<dyn Wallpaper + Send>>,
+    claimed_outputs: ClaimedOutputs,
+    /// 所有壁纸共用的解码器，相同视频在多个输出上只解码一次
+    decoder_pool: DecoderPool,
     is_running: Arc<Mutex<bool>>,
 }
 
@@ -15,51 +23,101 @@ impl Player {
     /// 创建新的播放器
     pub fn new() -> Self {
         Self {
-            wallpaper: None,
+            wallpapers: HashMap::new(),
+            claimed_outputs: ClaimedOutputs::default(),
+            decoder_pool: DecoderPool::new(),
             is_running: Arc::new(Mutex::new(false)),
         }
     }
 
+    /// 已单独指定壁纸的输出集合，`OutputTarget::All` 的壁纸会跳过这些输出
+    pub fn claimed_outputs(&self) -> ClaimedOutputs {
+        self.claimed_outputs.clone()
+    }
+
+    pub fn decoder_pool(&self) -> DecoderPool {
+        self.decoder_pool.clone()
+    }
+
+    /// 所有视频壁纸共用的音量和静音状态
+    pub fn audio_control(&self) -> AudioControl {
+        self.decoder_pool.audio_control()
+    }
+
     /// 设置壁纸
-    pub async fn set_wallpaper(&mut self, wallpaper: Box<dyn Wallpaper + Send>) {
-        // 停止当前壁纸（如果存在）
-        if let Some(mut w) = self.wallpaper.take() {
-            w.pause();
+    /// `OutputTarget::All` 会替换所有输出上的壁纸，指定输出只替换该输出的壁纸
+    pub async fn set_wallpaper(&mut self, target: OutputTarget, wallpaper: Box<dyn Wallpaper + Send>) {
+        match &target {
+            OutputTarget::All => {
+                // 停止所有壁纸（包括单独指定输出的壁纸）
+                for (_, mut w) in self.wallpapers.drain() {
+                    w.stop();
+                }
+                self.claimed_outputs.clear();
+            }
+            OutputTarget::Named(name) => {
+                // 停止该输出当前的壁纸（如果存在）
+                if let Some(mut w) = self.wallpapers.remove(&target) {
+                    w.stop();
+                }
+                self.claimed_outputs.claim(name);
+            }
         }
 
-        self.wallpaper = Some(wallpaper);
+        self.wallpapers.insert(target, wallpaper);
         *self.is_running.lock().await = true;
     }
 
     /// 播放壁纸
     pub async fn play(&mut self) {
-        if let Some(wallpaper) = &mut self.wallpaper {
+        for wallpaper in self.wallpapers.values_mut() {
             wallpaper.play();
+        }
+        if !self.wallpapers.is_empty() {
             *self.is_running.lock().await = true;
         }
     }
 
     /// 暂停壁纸
     pub async fn pause(&mut self) {
-        if let Some(wallpaper) = &mut self.wallpaper {
+        for wallpaper in self.wallpapers.values_mut() {
             wallpaper.pause();
+        }
+        if !self.wallpapers.is_empty() {
             *self.is_running.lock().await = false;
         }
     }
 
-    /// 运行壁纸（启动播放循环）
-    pub async fn run(&mut self) {
-        if let Some(wallpaper) = &mut self.wallpaper {
+    /// 运行指定输出目标的壁纸（启动播放循环）
+    pub async fn run(&mut self, target: &OutputTarget) {
+        if let Some(wallpaper) = self.wallpapers.get_mut(target) {
             wallpaper.run();
             *self.is_running.lock().await = true;
         }
     }
 
-    /// 停止壁纸
+    /// 停止所有壁纸
     pub async fn stop(&mut self) {
-        if let Some(wallpaper) = &mut self.wallpaper {
-            wallpaper.pause();
-            *self.is_running.lock().await = false;
+        for wallpaper in self.wallpapers.values_mut() {
+            wallpaper.stop();
+        }
+        *self.is_running.lock().await = false;
+    }
+
+    /// 设置画面效果，不重新开始播放
+    /// `OutputTarget::All` 修改所有壁纸，指定输出时只修改单独设置在该输出上的壁纸
+    pub async fn set_effects(&mut self, target: &OutputTarget, effects: Effects) -> Result<()> {
+        match target {
+            OutputTarget::All => {
+                for wallpaper in self.wallpapers.values_mut() {
+                    wallpaper.set_effects(effects.clone())?;
+                }
+                Ok(())
+            }
+            OutputTarget::Named(name) => match self.wallpapers.get_mut(target) {
+                Some(wallpaper) => wallpaper.set_effects(effects),
+                None => Err(anyhow::anyhow!("No wallpaper set on output {}, use `set --output {}` first", name, name)),
+            },
         }
     }
 
@@ -68,17 +126,23 @@ impl Player {
         *self.is_running.lock().await
     }
 
+    /// 获取所有壁纸的运行状态
+    pub async fn status(&self) -> Vec<WallpaperStatus> {
+        self.wallpapers.values().map(|w| w.status()).collect()
+    }
+
     /// 获取壁纸信息
     pub async fn info(&self) {
-        if let Some(wallpaper) = &self.wallpaper {
+        for wallpaper in self.wallpapers.values() {
             wallpaper.info();
         }
     }
 
-    /// 清除当前壁纸
+    /// 清除所有壁纸
     pub async fn clear(&mut self) {
         self.stop().await;
-        self.wallpaper = None;
+        self.wallpapers.clear();
+        self.claimed_outputs.clear();
     }
 }
 
@@ -86,4 +150,4 @@ impl Default for Player {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
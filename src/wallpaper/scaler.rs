@@ -0,0 +1,82 @@
+use anyhow::Result;
+use log::debug;
+
+use ffmpeg_next as ffmpeg;
+
+use ffmpeg::format::Pixel;
+use ffmpeg::software::scaling::{context::Context, flag::Flags};
+use ffmpeg::util::frame::video::Video;
+
+use crate::wayland::ScalingFilter;
+
+/// 缩放器的输入输出参数，任意一项变化时重建
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ScalerParams {
+    src_format: Pixel,
+    src_width: u32,
+    src_height: u32,
+    dst_format: Pixel,
+    dst_width: u32,
+    dst_height: u32,
+}
+
+/// 复用 sws 缩放器，只在输入格式、尺寸或目标尺寸变化时重建
+pub struct ScalerCache {
+    filter: ScalingFilter,
+    scaler: Option<(Context, ScalerParams)>,
+}
+
+impl ScalerCache {
+    pub fn new(filter: ScalingFilter) -> Self {
+        Self { filter, scaler: None }
+    }
+
+    /// 把 `input` 转换为 `dst_format` 并缩放到目标尺寸
+    pub fn run(&mut self, input: &Video, dst_format: Pixel, dst_width: u32, dst_height: u32) -> Result<Video> {
+        let params = ScalerParams {
+            src_format: input.format(),
+            src_width: input.width(),
+            src_height: input.height(),
+            dst_format,
+            dst_width,
+            dst_height,
+        };
+
+        let scaler = match &mut self.scaler {
+            Some((scaler, current)) if *current == params => scaler,
+            slot => {
+                debug!(
+                    "Creating scaler: {:?} {}x{} -> {:?} {}x{} ({})",
+                    params.src_format, params.src_width, params.src_height,
+                    params.dst_format, params.dst_width, params.dst_height, self.filter
+                );
+                let scaler = Context::get(
+                    params.src_format,
+                    params.src_width,
+                    params.src_height,
+                    params.dst_format,
+                    params.dst_width,
+                    params.dst_height,
+                    sws_flags(self.filter),
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to create scaler: {}", e))?;
+                &mut slot.insert((scaler, params)).0
+            }
+        };
+
+        let mut output = Video::empty();
+        scaler
+            .run(input, &mut output)
+            .map_err(|e| anyhow::anyhow!("Failed to scale frame: {}", e))?;
+        Ok(output)
+    }
+}
+
+fn sws_flags(filter: ScalingFilter) -> Flags {
+    match filter {
+        ScalingFilter::FastBilinear => Flags::FAST_BILINEAR,
+        ScalingFilter::Bilinear => Flags::BILINEAR,
+        ScalingFilter::Bicubic => Flags::BICUBIC,
+        ScalingFilter::Lanczos => Flags::LANCZOS,
+    }
+}
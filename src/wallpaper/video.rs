@@ -4,7 +4,8 @@ use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, mpsc};
 use tokio::task::JoinHandle;
 
-use crate::wallpaper::Wallpaper;
+use crate::wallpaper::effects::Effects;
+use crate::wallpaper::{Wallpaper, WallpaperStatus};
 use crate::wallpaper::{WallpaperType, project};
 use anyhow::Result;
 use ffmpeg_next as ffmpeg;
@@ -43,13 +44,6 @@ impl VideoWallpaper {
             wallpaper_type,
         }
     }
-
-    pub fn stop(&mut self) {
-        // Note: This is a synchronous method, so we can't use async here
-        // The actual stopping will be handled by the async tasks checking the flag
-        // This is a limitation of the current API design
-        info!("VideoWallpaper stop requested (async tasks will check flag)");
-    }
 }
 
 impl Wallpaper for VideoWallpaper {
@@ -93,7 +87,30 @@ impl Wallpaper for VideoWallpaper {
         self.render_task = Some(render_task);
     }
 
+    fn stop(&mut self) {
+        info!("VideoWallpaper stop requested");
+        if let Ok(mut is_stopped) = self.is_stopped.try_lock() {
+            *is_stopped = true;
+        }
+        if let Some(render_task) = self.render_task.take() {
+            render_task.abort();
+        }
+    }
+
     fn info(&self) {}
+
+    fn set_effects(&mut self, _effects: Effects) -> Result<()> {
+        Err(anyhow::anyhow!("Effects are not supported by this video backend"))
+    }
+
+    fn status(&self) -> WallpaperStatus {
+        WallpaperStatus {
+            path: self.video_path.clone(),
+            output: crate::wayland::OutputTarget::All.to_string(),
+            connection: crate::wayland::ConnectionState::Connected,
+            decoder: None,
+        }
+    }
 }
 
 async fn decode_video_async(
@@ -332,7 +349,11 @@ async fn render_frames_async(
     info!("Render thread started");
 
     // Initialize Wayland display
-    let mut wayland_app = match crate::wayland::WaylandApp::new() {
+    let mut wayland_app = match crate::wayland::WaylandApp::new(
+        crate::wayland::OutputTarget::All,
+        crate::wayland::ClaimedOutputs::default(),
+        crate::wayland::SurfaceOptions::default(),
+    ) {
         Ok(app) => app,
         Err(e) => {
             error!("Failed to initialize Wayland: {}", e);
@@ -404,7 +425,7 @@ async fn render_frames_async(
 
                 // Render frame to Wayland surface
                 if let Err(e) =
-                    wayland_app.render_frame(&frame_data.frame, frame_data.width, frame_data.height)
+                    wayland_app.render_frame(&frame_data.frame, frame_data.width, frame_data.height, crate::wayland::PixelFormat::Argb8888)
                 {
                     error!("Failed to render frame: {}", e);
                 }
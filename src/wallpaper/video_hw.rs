@@ -1,479 +1,95 @@
 use log::{error, info, warn, debug};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::{Mutex, mpsc};
+use tokio::sync::{Mutex, mpsc, watch};
 use tokio::task::JoinHandle;
 
-use crate::wallpaper::Wallpaper;
+use crate::wallpaper::audio::AudioOutput;
+use crate::wallpaper::clock::{FrameSchedule, PlaybackClock};
+use crate::wallpaper::decoder_pool::{DecoderKey, DecoderPool, SharedLink};
+use crate::wallpaper::devices::DeviceSelector;
+use crate::wallpaper::hwaccel::{HardwareAcceleration, open_video_decoder};
+use crate::wallpaper::frame_rate::FrameRateConverter;
+use crate::wallpaper::filter::{GpuScaler, VideoFilter};
+use crate::wallpaper::effects::Effects;
+use crate::wallpaper::scaler::ScalerCache;
+use crate::wallpaper::dmabuf::DmabufFrame;
+use crate::wallpaper::{Wallpaper, WallpaperStatus};
 use crate::wallpaper::{WallpaperType, project};
+use crate::wayland::{
+    ClaimedOutputs, ConnectionState, DmabufFormat, OutputTarget, PixelFormat, ScalingMode, SurfaceOptions, WaylandApp,
+    WaylandEvent,
+};
 use anyhow::Result;
 use ffmpeg_next as ffmpeg;
 
+use ffmpeg::codec::discard::Discard;
 use ffmpeg::format::input;
 use ffmpeg::media::Type;
-use ffmpeg::software::scaling::{context::Context, flag::Flags};
 use ffmpeg::util::frame::video::Video;
 
-/// 硬件加速类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum HardwareAcceleration {
-    /// VAAPI (Video Acceleration API) - Linux Intel/AMD GPU
-    VAAPI,
-    /// CUDA - NVIDIA GPU
-    CUDA,
-    /// VDPAU (Video Decode and Presentation API for Unix) - Linux NVIDIA GPU
-    VDPAU,
-    /// QSV (Quick Sync Video) - Intel GPU
-    QSV,
-    /// VideoToolbox - macOS
-    VideoToolbox,
-    /// D3D11VA - Windows
-    D3D11VA,
-    /// 无硬件加速（软件解码）
-    None,
-}
-
-impl HardwareAcceleration {
-    /// 获取硬件设备的名称
-    pub fn device_name(&self) -> &'static str {
-        match self {
-            HardwareAcceleration::VAAPI => "/dev/dri/renderD128",  // VAAPI 需要设备路径
-            HardwareAcceleration::CUDA => "cuda",
-            HardwareAcceleration::VDPAU => "vdpau",
-            HardwareAcceleration::QSV => "qsv",
-            HardwareAcceleration::VideoToolbox => "videotoolbox",
-            HardwareAcceleration::D3D11VA => "d3d11va",
-            HardwareAcceleration::None => "",
-        }
-    }
-
-    /// 获取 FFmpeg AVHWDeviceType
-    pub fn av_hwdevice_type(&self) -> ffmpeg::ffi::AVHWDeviceType {
-        match self {
-            HardwareAcceleration::VAAPI => ffmpeg::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI,
-            HardwareAcceleration::CUDA => ffmpeg::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA,
-            HardwareAcceleration::VDPAU => ffmpeg::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VDPAU,
-            HardwareAcceleration::QSV => ffmpeg::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_QSV,
-            HardwareAcceleration::VideoToolbox => ffmpeg::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VIDEOTOOLBOX,
-            HardwareAcceleration::D3D11VA => ffmpeg::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_D3D11VA,
-            HardwareAcceleration::None => ffmpeg::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_NONE,
-        }
-    }
-
-    /// 获取硬件像素格式
-    pub fn hw_pixel_format(&self) -> ffmpeg::format::Pixel {
-        match self {
-            HardwareAcceleration::VAAPI => ffmpeg::format::Pixel::VAAPI,
-            HardwareAcceleration::CUDA => ffmpeg::format::Pixel::CUDA,
-            HardwareAcceleration::VDPAU => ffmpeg::format::Pixel::VDPAU,
-            HardwareAcceleration::QSV => ffmpeg::format::Pixel::QSV,
-            HardwareAcceleration::VideoToolbox => ffmpeg::format::Pixel::VIDEOTOOLBOX,
-            HardwareAcceleration::D3D11VA => ffmpeg::format::Pixel::D3D11,
-            HardwareAcceleration::None => panic!("None has no hw pixel format"),
-        }
-    }
-}
-
-/// 硬件解码器包装器
-pub struct HardwareDecoder {
-    hw_device_ctx: Option<*mut ffmpeg::ffi::AVBufferRef>,
-    hw_frames_ctx: Option<*mut ffmpeg::ffi::AVBufferRef>,
-    hw_accel_type: HardwareAcceleration,
-}
-
-unsafe impl Send for HardwareDecoder {}
-unsafe impl Sync for HardwareDecoder {}
-
-impl HardwareDecoder {
-    /// 创建新的硬件解码器
-    pub fn new(hw_accel_type: HardwareAcceleration) -> Result<Self> {
-        match hw_accel_type {
-            HardwareAcceleration::None => Ok(Self {
-                hw_device_ctx: None,
-                hw_frames_ctx: None,
-                hw_accel_type,
-            }),
-            _ => {
-                let device_name = hw_accel_type.device_name();
-                let hw_device_type = hw_accel_type.av_hwdevice_type();
-                info!("Initializing hardware device: {} (type: {:?})", device_name, hw_device_type);
-
-                let mut hw_device_ctx_ptr: *mut ffmpeg::ffi::AVBufferRef = std::ptr::null_mut();
-
-                // 将 device_name 转换为 CString 以确保正确的 null 终止
-                let device_name_cstr = std::ffi::CString::new(device_name)
-                    .map_err(|e| anyhow::anyhow!("Failed to create CString: {}", e))?;
-
-                // 调用 FFmpeg 的 av_hwdevice_ctx_create
-                let ret = unsafe {
-                    ffmpeg::ffi::av_hwdevice_ctx_create(
-                        &mut hw_device_ctx_ptr,
-                        hw_device_type,
-                        device_name_cstr.as_ptr(),
-                        std::ptr::null_mut(),
-                        0,
-                    )
-                };
-
-                if ret < 0 {
-                    return Err(anyhow::anyhow!(
-                        "Failed to create hardware device context: error code {}",
-                        ret
-                    ));
-                }
-
-                if hw_device_ctx_ptr.is_null() {
-                    return Err(anyhow::anyhow!(
-                        "Hardware device context is null after creation"
-                    ));
-                }
-
-                info!("Hardware device context created successfully");
-
-                Ok(Self {
-                    hw_device_ctx: Some(hw_device_ctx_ptr),
-                    hw_frames_ctx: None,
-                    hw_accel_type,
-                })
-            }
-        }
-    }
-
-    /// 配置解码器使用硬件加速
-    pub fn configure_decoder(
-        &mut self,
-        decoder: &mut ffmpeg::codec::decoder::Video,
-    ) -> Result<()> {
-        if self.hw_accel_type == HardwareAcceleration::None {
-            return Ok(());
-        }
-
-        info!("Configuring decoder for hardware acceleration");
-
-        unsafe {
-            let codec_ctx = decoder.as_mut_ptr();
-            let hw_device_ctx = self.hw_device_ctx.unwrap();
-
-            // 设置硬件设备上下文
-            (*codec_ctx).hw_device_ctx = ffmpeg::ffi::av_buffer_ref(hw_device_ctx);
-
-            if (*codec_ctx).hw_device_ctx.is_null() {
-                return Err(anyhow::anyhow!(
-                    "Failed to set hw_device_ctx in decoder context"
-                ));
-            }
-
-            info!("Hardware device context set in decoder");
-        }
-
-        Ok(())
-    }
-
-    /// 从硬件帧传输到软件帧
-    pub fn transfer_frame(&self, hw_frame: &Video, sw_frame: &mut Video) -> Result<()> {
-        if self.hw_accel_type == HardwareAcceleration::None {
-            return Err(anyhow::anyhow!("No hardware acceleration enabled"));
-        }
-
-        unsafe {
-            let hw_frame_ptr = hw_frame.as_ptr();
-            let sw_frame_ptr = sw_frame.as_mut_ptr();
-
-            let ret = ffmpeg::ffi::av_hwframe_transfer_data(
-                sw_frame_ptr,
-                hw_frame_ptr,
-                0,
-            );
-
-            if ret < 0 {
-                return Err(anyhow::anyhow!(
-                    "Failed to transfer frame from hardware to software: error code {}",
-                    ret
-                ));
-            }
-
-            // 复制帧属性（时间戳等）
-            let ret = ffmpeg::ffi::av_frame_copy_props(sw_frame_ptr, hw_frame_ptr);
-            if ret < 0 {
-                return Err(anyhow::anyhow!(
-                    "Failed to copy frame properties: error code {}",
-                    ret
-                ));
-            }
-        }
-
-        Ok(())
-    }
-
-    /// 在 GPU 上缩放硬件帧（仅支持 VAAPI）
-    /// 使用 VAAPI VPP (Video Post Processing) 进行硬件缩放
-    pub fn scale_frame_gpu(&self, src_frame: &Video, dst_frame: &mut Video, dst_width: i32, dst_height: i32) -> Result<()> {
-        if self.hw_accel_type != HardwareAcceleration::VAAPI {
-            return Err(anyhow::anyhow!("GPU scaling only supported for VAAPI"));
-        }
-
-        // 使用 FFmpeg 的 scale_vaapi filter 进行硬件缩放
-        // 简化版本：直接在硬件帧上操作
-        unsafe {
-            // 创建 filter graph
-            let mut graph_ptr: *mut ffmpeg::ffi::AVFilterGraph = std::ptr::null_mut();
-            graph_ptr = ffmpeg::ffi::avfilter_graph_alloc();
-            if graph_ptr.is_null() {
-                return Err(anyhow::anyhow!("Failed to allocate filter graph"));
-            }
-
-            // 创建 buffer filter (输入) - 使用硬件帧格式
-            let buffer_src = ffmpeg::ffi::avfilter_get_by_name(b"buffer\0".as_ptr() as *const i8);
-            if buffer_src.is_null() {
-                return Err(anyhow::anyhow!("Failed to find buffer filter"));
-            }
-
-            let mut buffer_src_ctx: *mut ffmpeg::ffi::AVFilterContext = std::ptr::null_mut();
-            
-            // 获取源帧的实际格式
-            let src_format = src_frame.format();
-            let src_format_str = match src_format {
-                ffmpeg::format::Pixel::NV12 => "nv12",
-                ffmpeg::format::Pixel::YUV420P => "yuv420p",
-                _ => "nv12",  // 默认使用 nv12
-            };
-            
-            let args = format!(
-                "video_size={}x{}:pix_fmt={}:time_base=1/30:pixel_aspect=1/1",
-                src_frame.width(),
-                src_frame.height(),
-                src_format_str
-            );
-            let args_cstr = std::ffi::CString::new(args).unwrap();
-            
-            let ret = ffmpeg::ffi::avfilter_graph_create_filter(
-                &mut buffer_src_ctx,
-                buffer_src,
-                b"in\0".as_ptr() as *const i8,
-                args_cstr.as_ptr(),
-                std::ptr::null_mut(),
-                graph_ptr,
-            );
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to create buffer source filter: {}", ret));
-            }
-
-            // 创建 hwupload filter (将软件帧上传到硬件)
-            let hwupload = ffmpeg::ffi::avfilter_get_by_name(b"hwupload\0".as_ptr() as *const i8);
-            if hwupload.is_null() {
-                return Err(anyhow::anyhow!("Failed to find hwupload filter"));
-            }
-
-            let mut hwupload_ctx: *mut ffmpeg::ffi::AVFilterContext = std::ptr::null_mut();
-            // hwupload filter 不需要创建时的参数
-            let ret = ffmpeg::ffi::avfilter_graph_create_filter(
-                &mut hwupload_ctx,
-                hwupload,
-                b"upload\0".as_ptr() as *const i8,
-                std::ptr::null(),
-                std::ptr::null_mut(),
-                graph_ptr,
-            );
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to create hwupload filter: {}", ret));
-            }
-
-            // 设置硬件设备上下文到 hwupload filter 的私有数据
-            if let Some(hw_device_ctx) = self.hw_device_ctx {
-                // 获取 hwupload filter 的私有数据
-                let hwupload_priv = (*hwupload_ctx).priv_;
-                if hwupload_priv.is_null() {
-                    return Err(anyhow::anyhow!("Failed to get hwupload private data"));
-                }
-                
-                // 直接设置 hw_device_ctx 字段
-                // FFmpeg 的 hwupload filter 私有数据的第一个字段就是 hw_device_ctx
-                *(hwupload_priv as *mut *mut ffmpeg::ffi::AVBufferRef) = hw_device_ctx;
-            } else {
-                return Err(anyhow::anyhow!("No hardware device context available"));
-            }
-
-            // 创建 scale_vaapi filter
-            let scale_vaapi = ffmpeg::ffi::avfilter_get_by_name(b"scale_vaapi\0".as_ptr() as *const i8);
-            if scale_vaapi.is_null() {
-                return Err(anyhow::anyhow!("Failed to find scale_vaapi filter"));
-            }
-
-            let mut scale_ctx: *mut ffmpeg::ffi::AVFilterContext = std::ptr::null_mut();
-            let scale_args = format!("w={}:h={}:mode=fast", dst_width, dst_height);
-            let scale_args_cstr = std::ffi::CString::new(scale_args).unwrap();
-            
-            let ret = ffmpeg::ffi::avfilter_graph_create_filter(
-                &mut scale_ctx,
-                scale_vaapi,
-                b"scale\0".as_ptr() as *const i8,
-                scale_args_cstr.as_ptr(),
-                std::ptr::null_mut(),
-                graph_ptr,
-            );
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to create scale_vaapi filter: {}", ret));
-            }
-
-            // 创建 hwdownload filter (将硬件帧下载到软件帧)
-            let hwdownload = ffmpeg::ffi::avfilter_get_by_name(b"hwdownload\0".as_ptr() as *const i8);
-            if hwdownload.is_null() {
-                return Err(anyhow::anyhow!("Failed to find hwdownload filter"));
-            }
-
-            let mut hwdownload_ctx: *mut ffmpeg::ffi::AVFilterContext = std::ptr::null_mut();
-            let ret = ffmpeg::ffi::avfilter_graph_create_filter(
-                &mut hwdownload_ctx,
-                hwdownload,
-                b"download\0".as_ptr() as *const i8,
-                std::ptr::null(),
-                std::ptr::null_mut(),
-                graph_ptr,
-            );
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to create hwdownload filter: {}", ret));
-            }
-
-            // 创建 format filter (转换为 BGRA)
-            let format_filter = ffmpeg::ffi::avfilter_get_by_name(b"format\0".as_ptr() as *const i8);
-            if format_filter.is_null() {
-                return Err(anyhow::anyhow!("Failed to find format filter"));
-            }
-
-            let mut format_ctx: *mut ffmpeg::ffi::AVFilterContext = std::ptr::null_mut();
-            let format_args = "pix_fmts=bgra";
-            let format_args_cstr = std::ffi::CString::new(format_args).unwrap();
-            
-            let ret = ffmpeg::ffi::avfilter_graph_create_filter(
-                &mut format_ctx,
-                format_filter,
-                b"format\0".as_ptr() as *const i8,
-                format_args_cstr.as_ptr(),
-                std::ptr::null_mut(),
-                graph_ptr,
-            );
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to create format filter: {}", ret));
-            }
-
-            // 创建 buffersink filter (输出)
-            let buffersink = ffmpeg::ffi::avfilter_get_by_name(b"buffersink\0".as_ptr() as *const i8);
-            if buffersink.is_null() {
-                return Err(anyhow::anyhow!("Failed to find buffersink filter"));
-            }
-
-            let mut buffersink_ctx: *mut ffmpeg::ffi::AVFilterContext = std::ptr::null_mut();
-            let ret = ffmpeg::ffi::avfilter_graph_create_filter(
-                &mut buffersink_ctx,
-                buffersink,
-                b"out\0".as_ptr() as *const i8,
-                std::ptr::null(),
-                std::ptr::null_mut(),
-                graph_ptr,
-            );
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to create buffersink filter: {}", ret));
-            }
-
-            // 连接 filters: buffer_src -> hwupload -> scale_vaapi -> hwdownload -> format -> buffersink
-            let ret = ffmpeg::ffi::avfilter_link(buffer_src_ctx, 0, hwupload_ctx, 0);
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to link buffer_src to hwupload: {}", ret));
-            }
-
-            let ret = ffmpeg::ffi::avfilter_link(hwupload_ctx, 0, scale_ctx, 0);
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to link hwupload to scale_vaapi: {}", ret));
-            }
-
-            let ret = ffmpeg::ffi::avfilter_link(scale_ctx, 0, hwdownload_ctx, 0);
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to link scale_vaapi to hwdownload: {}", ret));
-            }
-
-            let ret = ffmpeg::ffi::avfilter_link(hwdownload_ctx, 0, format_ctx, 0);
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to link hwdownload to format: {}", ret));
-            }
-
-            let ret = ffmpeg::ffi::avfilter_link(format_ctx, 0, buffersink_ctx, 0);
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to link format to buffersink: {}", ret));
-            }
-
-            // 配置 filter graph
-            let ret = ffmpeg::ffi::avfilter_graph_config(graph_ptr, std::ptr::null_mut());
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to configure filter graph: {}", ret));
-            }
-
-            // 将输入帧添加到 filter graph
-            let src_ptr = src_frame.as_ptr();
-            let ret = ffmpeg::ffi::av_buffersrc_add_frame_flags(buffer_src_ctx, src_ptr as *mut ffmpeg::ffi::AVFrame, 0);
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to add frame to buffer src: {}", ret));
-            }
-
-            // 从 filter graph 获取输出帧
-            let dst_ptr = dst_frame.as_mut_ptr();
-            let ret = ffmpeg::ffi::av_buffersink_get_frame(buffersink_ctx, dst_ptr);
-            if ret < 0 {
-                return Err(anyhow::anyhow!("Failed to get frame from buffer sink: {}", ret));
-            }
-
-            // 清理 filter graph
-            ffmpeg::ffi::avfilter_graph_free(&mut graph_ptr);
-
-            Ok(())
-        }
-    }
-}
-
-impl Drop for HardwareDecoder {
-    fn drop(&mut self) {
-        if let Some(mut hw_device_ctx) = self.hw_device_ctx {
-            unsafe {
-                ffmpeg::ffi::av_buffer_unref(&mut hw_device_ctx);
-            }
-        }
-        if let Some(mut hw_frames_ctx) = self.hw_frames_ctx {
-            unsafe {
-                ffmpeg::ffi::av_buffer_unref(&mut hw_frames_ctx);
-            }
-        }
-    }
-}
-
 pub struct VideoWallpaper {
     video_path: String,
     is_paused: Arc<Mutex<bool>>,
     is_stopped: Arc<Mutex<bool>>,
-    decode_task: Option<JoinHandle<()>>,
     render_task: Option<JoinHandle<()>>,
     project: Option<project::Project>,
     wallpaper_type: WallpaperType,
     hw_accel_type: HardwareAcceleration,
+    device: Option<DeviceSelector>,  // 解码设备，None 时自动选择
     target_fps: u32,  // 目标帧率，默认 30fps
     skip_frames: u32,  // 跳帧计数
     max_width: u32,  // 最大宽度，0 表示不限制
     max_height: u32,  // 最大高度，0 表示不限制
+    output_target: OutputTarget,
+    claimed_outputs: ClaimedOutputs,
+    surface_options: SurfaceOptions,
+    connection_state: Arc<std::sync::Mutex<ConnectionState>>,
+    decoder_pool: DecoderPool,
+    audio_output: AudioOutput,
+    decoder_link: Option<SharedLink>,  // 运行后所在的解码器，用于显示解码后端
+    effects: Effects,
+    subscriber_id: Option<u64>,  // 运行后的订阅，用于在播放中修改画面效果
+}
+
+/// DMA-BUF 模式下帧通道的容量，每个排队的帧都占用一个硬件 surface
+const DMABUF_QUEUE_FRAMES: usize = 4;
+
+/// 渲染任务反馈给解码任务的信息
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderHints {
+    /// 输出的物理像素尺寸，用作缩放目标
+    pub target_size: Option<(u32, u32)>,
+    /// compositor 可以导入的 DMA-BUF 格式，为空时使用 SHM
+    pub dmabuf_formats: Vec<DmabufFormat>,
+    /// SHM buffer 的像素格式，缩放器输出对应的格式
+    pub pixel_format: PixelFormat,
 }
 
 pub struct FrameData {
     frame: Vec<u8>,
     width: u32,
     height: u32,
-    frame_time: u32, // in milliseconds
+    format: PixelFormat,
+    /// 显示时间戳（换算成时长），按它安排显示时间，音频也按同一时间轴同步
+    pts: Duration,
+    /// 视频每循环（或 seek）一次加一，不同 generation 的 PTS 不可比较
+    generation: u64,
+    /// 零拷贝路径：导出为 DMA-BUF 的硬件帧，此时 frame 为空
+    dmabuf: Option<DmabufFrame>,
 }
 
 impl FrameData {
     /// 创建新的帧数据（零拷贝）
-    pub fn new(frame: Vec<u8>, width: u32, height: u32, frame_time: u32) -> Self {
+    pub fn new(frame: Vec<u8>, width: u32, height: u32, format: PixelFormat) -> Self {
         Self {
             frame,
             width,
             height,
-            frame_time,
+            format,
+            pts: Duration::ZERO,
+            generation: 0,
+            dmabuf: None,
         }
     }
     
@@ -489,15 +105,24 @@ impl VideoWallpaper {
             video_path,
             is_paused: Arc::new(Mutex::new(false)),
             is_stopped: Arc::new(Mutex::new(false)),
-            decode_task: None,
             render_task: None,
             project: None,
             wallpaper_type,
-            hw_accel_type: HardwareAcceleration::VAAPI, // 默认使用 VAAPI
+            hw_accel_type: HardwareAcceleration::Auto, // 默认自动探测，失败时软件解码
+            device: None,
             target_fps: 30,  // 默认 30fps，减少 CPU 占用
             skip_frames: 0,
-            max_width: 1920,  // 默认最大宽度 1920
-            max_height: 1080,  // 默认最大高度 1080
+            max_width: 0,  // 默认不限制，缩放到输出的物理像素尺寸
+            max_height: 0,
+            output_target: OutputTarget::All,
+            claimed_outputs: ClaimedOutputs::default(),
+            surface_options: SurfaceOptions::default(),
+            connection_state: Arc::new(std::sync::Mutex::new(ConnectionState::Connecting)),
+            decoder_pool: DecoderPool::new(),
+            audio_output: AudioOutput::Off,
+            decoder_link: None,
+            effects: Effects::default(),
+            subscriber_id: None,
         }
     }
 
@@ -523,8 +148,30 @@ impl VideoWallpaper {
         self.hw_accel_type = hw_accel_type;
     }
 
-    pub fn stop(&mut self) {
-        info!("VideoWallpaper stop requested (async tasks will check flag)");
+    /// 设置解码设备（render node 路径、PCI ID 或驱动名），多 GPU 时选择解码用的 GPU
+    pub fn set_decode_device(&mut self, device: Option<DeviceSelector>) {
+        self.device = device;
+    }
+
+    /// 设置显示壁纸的输出
+    pub fn set_output(&mut self, target: OutputTarget, claimed: ClaimedOutputs) {
+        self.output_target = target;
+        self.claimed_outputs = claimed;
+    }
+
+    /// 设置缩放方式和背景色
+    pub fn set_surface_options(&mut self, options: SurfaceOptions) {
+        self.surface_options = options;
+    }
+
+    /// 设置共享的解码器池，相同视频和解码参数的壁纸只解码一次
+    pub fn set_decoder_pool(&mut self, pool: DecoderPool) {
+        self.decoder_pool = pool;
+    }
+
+    /// 设置音频输出，默认不播放声音
+    pub fn set_audio_output(&mut self, output: AudioOutput) {
+        self.audio_output = output;
     }
 }
 
@@ -538,29 +185,40 @@ impl Wallpaper for VideoWallpaper {
     }
 
     fn run(&mut self) {
-        let (tx, rx) = mpsc::channel::<FrameData>(60);
-        let video_path = self.video_path.clone();
-        let is_paused = self.is_paused.clone();
-        let is_stopped = self.is_stopped.clone();
-        let hw_accel_type = self.hw_accel_type;
-        let target_fps = self.target_fps;
-        let max_width = self.max_width;
-        let max_height = self.max_height;
-
-        let is_paused_render = is_paused.clone();
-        let is_stopped_render = is_stopped.clone();
+        // VAAPI 可能走 DMA-BUF 零拷贝路径，排队的帧会占用硬件 surface，因此缩短队列
+        let queue_frames = match self.hw_accel_type {
+            HardwareAcceleration::Auto | HardwareAcceleration::VAAPI => DMABUF_QUEUE_FRAMES,
+            _ => 60,
+        };
+        let surface_options = self.surface_options.clone();
+        let key = DecoderKey {
+            video_path: self.video_path.clone(),
+            hw_accel_type: self.hw_accel_type,
+            device: self.device.clone(),
+            target_fps: self.target_fps,
+            max_width: self.max_width,
+            max_height: self.max_height,
+            scaling: surface_options.scaling,
+            quality: surface_options.quality,
+            scaling_filter: surface_options.scaling_filter,
+            audio: self.audio_output.clone(),
+            filter: self.effects.filtergraph(),
+        };
+        // 渲染任务把输出的物理像素尺寸和 DMA-BUF 支持情况告诉解码任务；
+        // 已有相同参数的解码器时直接订阅它的帧
+        let subscription = self.decoder_pool.subscribe(key, queue_frames);
+        self.decoder_link = Some(subscription.link.clone());
+        self.subscriber_id = Some(subscription.subscriber_id);
+
+        let is_paused_render = self.is_paused.clone();
+        let is_stopped_render = self.is_stopped.clone();
+        let output_target = self.output_target.clone();
+        let claimed_outputs = self.claimed_outputs.clone();
+        let connection_state = self.connection_state.clone();
 
         let handle = tokio::runtime::Handle::current();
-
-        let decode_task = handle.spawn(async move {
-            if let Err(e) = decode_video_async(&video_path, tx, is_paused, is_stopped, hw_accel_type, target_fps, max_width, max_height).await {
-                error!("Video decode error: {}", e);
-            }
-        });
-        self.decode_task = Some(decode_task);
-
         let render_task = handle.spawn(async move {
-            render_frames_async(rx, is_paused_render, is_stopped_render).await;
+            render_frames_async(subscription.frames, subscription.hints, subscription.link, is_paused_render, is_stopped_render, output_target, claimed_outputs, surface_options, connection_state).await;
         });
         self.render_task = Some(render_task);
         
@@ -568,22 +226,68 @@ impl Wallpaper for VideoWallpaper {
         std::thread::sleep(std::time::Duration::from_millis(10));
     }
 
+    fn stop(&mut self) {
+        info!("VideoWallpaper stop requested");
+        match self.is_stopped.try_lock() {
+            Ok(mut is_stopped) => *is_stopped = true,
+            Err(_) => {
+                let is_stopped = self.is_stopped.clone();
+                tokio::spawn(async move {
+                    *is_stopped.lock().await = true;
+                });
+            }
+        }
+        // 结束渲染任务会关闭帧通道并退订，没有其他订阅者时解码任务随之退出；
+        // WaylandApp 被释放后 surface 也会被销毁
+        if let Some(render_task) = self.render_task.take() {
+            render_task.abort();
+        }
+    }
+
     fn info(&self) {}
+
+    fn set_effects(&mut self, effects: Effects) -> Result<()> {
+        // 运行前只记录下来，run 时作为解码参数；运行中由解码器池修改，不影响共用解码器的其他输出
+        if let Some(id) = self.subscriber_id {
+            self.decoder_pool.set_filter(id, effects.filtergraph());
+        }
+        self.effects = effects;
+        Ok(())
+    }
+
+    fn status(&self) -> WallpaperStatus {
+        WallpaperStatus {
+            path: self.video_path.clone(),
+            output: self.output_target.to_string(),
+            connection: *self.connection_state.lock().unwrap(),
+            decoder: self.decoder_link.as_ref().and_then(|link| link.lock().unwrap().backend.lock().unwrap().clone()),
+        }
+    }
 }
 
-async fn decode_video_async(
-    video_path: &str,
+/// 按 `key` 中的解码参数解码视频，帧发送到 `tx`，实际使用的解码后端写入 `backend`
+pub(crate) async fn decode_video_async(
+    key: DecoderKey,
     tx: mpsc::Sender<FrameData>,
+    mut hints_rx: watch::Receiver<RenderHints>,
+    mut filter_rx: watch::Receiver<Option<String>>,
     is_paused: Arc<Mutex<bool>>,
     is_stopped: Arc<Mutex<bool>>,
-    hw_accel_type: HardwareAcceleration,
-    target_fps: u32,
-    max_width: u32,
-    max_height: u32,
+    backend: DecoderBackend,
 ) -> Result<()> {
-    debug!("decode_video_async started with hardware acceleration: {:?}, target_fps: {}, max_resolution: {}x{}",
-           hw_accel_type, target_fps, max_width, max_height);
-    let video_path = video_path.to_string();
+    let DecoderKey {
+        video_path,
+        hw_accel_type,
+        device,
+        target_fps,
+        max_width,
+        max_height,
+        scaling,
+        scaling_filter,
+        ..
+    } = key;
+    debug!("decode_video_async started with hardware acceleration: {}, device: {:?}, target_fps: {}, max_resolution: {}x{}",
+           hw_accel_type, device, target_fps, max_width, max_height);
     // 使用视频原始尺寸，让 Wayland viewporter 处理缩放
     let output_width = 0u32;  // 将在解码后获取
     let output_height = 0u32;
@@ -615,48 +319,33 @@ async fn decode_video_async(
         let time_base = input_stream.time_base();
         debug!("Stream time base: {}/{}", time_base.numerator(), time_base.denominator());
 
-        // Create decoder
-        let context_decoder = ffmpeg::codec::context::Context::from_parameters(input_stream.parameters())
-            .map_err(|e| anyhow::anyhow!("Failed to create decoder context: {}", e))?;
-        let mut decoder = context_decoder.decoder().video()
-            .map_err(|e| anyhow::anyhow!("Failed to create video decoder: {}", e))?;
-
-        debug!("Decoder created successfully");
-
-        // Initialize hardware decoder if enabled
-        let mut hw_decoder = HardwareDecoder::new(hw_accel_type)?;
-        hw_decoder.configure_decoder(&mut decoder)?;
-        debug!("Hardware decoder configured: {:?}", hw_accel_type);
+        // Create decoder，按偏好探测硬件加速，不可用时回退到下一个
+        let (mut decoder, hw_decoder) = open_video_decoder(&mut ictx, video_stream_index, hw_accel_type, device.as_ref())?;
+        *backend.lock().unwrap() = Some(hw_decoder.to_string());
 
         info!("Video opened: {}x{}, target_fps: {}",
               decoder.width(), decoder.height(), target_fps);
 
         let mut frame_count = 0u64;
-        let mut last_pts: Option<i64> = None;
-        let mut frame_time_ms: u32 = 33;
-        let mut skip_counter = 0u32;  // 跳帧计数器
-        let target_frame_interval = 1000 / target_fps;  // 目标帧间隔（毫秒）
-
-        // 计算缩放比例
-        let (scale_width, scale_height) = if max_width > 0 && max_height > 0 {
-            let orig_width = decoder.width();
-            let orig_height = decoder.height();
-            
-            // 计算缩放比例，保持宽高比
-            let scale_x = max_width as f32 / orig_width as f32;
-            let scale_y = max_height as f32 / orig_height as f32;
-            let scale = scale_x.min(scale_y).min(1.0);  // 不放大，只缩小
-            
-            let new_width = (orig_width as f32 * scale) as u32;
-            let new_height = (orig_height as f32 * scale) as u32;
-            
-            info!("Resolution scaling: {}x{} -> {}x{} (scale: {:.2})",
-                  orig_width, orig_height, new_width, new_height, scale);
-            
-            (Some(new_width), Some(new_height))
-        } else {
-            (None, None)
-        };
+        let mut generation = 0u64;
+        // 按时间戳均匀选帧，达到目标帧率
+        let mut frame_rate = FrameRateConverter::new(target_fps);
+        let mut skipped_frames = 0u64;
+        let pts_to_duration =
+            move |pts: i64| Duration::from_secs_f64((pts as f64 * f64::from(time_base)).max(0.0));
+
+        // 计算缩放比例，输出尺寸变化时重新计算
+        // fill 模式会裁剪，需要覆盖整个输出而不是完整放入
+        let orig_width = decoder.width();
+        let orig_height = decoder.height();
+        let cover = matches!(scaling, ScalingMode::Fill | ScalingMode::Span);
+        let mut hints = hints_rx.borrow_and_update().clone();
+        let mut dmabuf_export_failed = false;
+        let (mut scale_width, mut scale_height) =
+            match scaled_size(orig_width, orig_height, hints.target_size, max_width, max_height, cover) {
+                Some((w, h)) => (Some(w), Some(h)),
+                None => (None, None),
+            };
 
         let rt = tokio::runtime::Runtime::new()
             .map_err(|e| anyhow::anyhow!("Failed to create runtime: {}", e))?;
@@ -664,6 +353,11 @@ async fn decode_video_async(
         let result = rt.block_on(async move {
             let mut decoder = decoder;
             let mut first_decoded = false;
+            let mut scaler = ScalerCache::new(scaling_filter);
+            // 硬件帧先在 GPU 上缩小再下载，失败时本次播放改用 CPU 缩放
+            let mut gpu_scaler = GpuScaler::new(hw_decoder.hw_accel_type(), scaling_filter);
+            // 画面效果，在转换为 SHM 像素格式之前应用，可以在播放中通过 IPC 修改
+            let mut video_filter = VideoFilter::new(filter_rx.borrow_and_update().clone());
 
             info!("Starting decode loop with target_fps: {}...", target_fps);
             let mut packet_count = 0u64;
@@ -686,9 +380,11 @@ async fn decode_video_async(
                     None => {
                         debug!("Video ended, seeking to beginning");
                         let _ = ictx.seek(0, ..);
+                        // 丢弃解码器里上一轮的帧，新一轮的帧使用新的 generation
+                        decoder.flush();
+                        generation += 1;
                         frame_count = 0;
-                        last_pts = None;
-                        frame_time_ms = 33;
+                        frame_rate.reset();
                         continue;
                     }
                 };
@@ -699,11 +395,30 @@ async fn decode_video_async(
                 }
 
                 if stream.index() == video_stream_index {
+                    // 一定会被丢弃的帧，如果不被其他帧参考就不解码
+                    let discard = match packet.pts() {
+                        Some(pts) if !frame_rate.would_accept(pts_to_duration(pts)) => Discard::NonReference,
+                        _ => Discard::Default,
+                    };
+                    decoder.skip_frame(discard);
                     if let Err(e) = decoder.send_packet(&packet) {
                         error!("Failed to send packet to decoder: {}", e);
                         break Err(anyhow::anyhow!("Decoder error"));
                     }
 
+                    // 输出的物理尺寸变化（缩放、热插拔）时更新缩放目标
+                    if hints_rx.has_changed().unwrap_or(false) {
+                        hints = hints_rx.borrow_and_update().clone();
+                        (scale_width, scale_height) =
+                            match scaled_size(orig_width, orig_height, hints.target_size, max_width, max_height, cover) {
+                                Some((w, h)) => (Some(w), Some(h)),
+                                None => (None, None),
+                            };
+                    }
+                    if filter_rx.has_changed().unwrap_or(false) {
+                        video_filter.set_spec(filter_rx.borrow_and_update().clone());
+                    }
+
                     let mut decoded = Video::empty();
                     match decoder.receive_frame(&mut decoded) {
                         Ok(_) => {
@@ -714,115 +429,112 @@ async fn decode_video_async(
 
                             frame_count += 1;
 
-                            // 跳帧逻辑：根据目标帧率跳过部分帧
-                            skip_counter += 1;
-                            let video_fps = if frame_time_ms > 0 { 1000 / frame_time_ms } else { 60 };
-                            let skip_ratio = if video_fps > target_fps {
-                                (video_fps as f32 / target_fps as f32).ceil() as u32
-                            } else {
-                                1
-                            };
-
-                            // 跳过不需要的帧
-                            if skip_counter % skip_ratio != 0 {
+                            let presentation_time = pts_to_duration(pts);
+                            if !frame_rate.accept(presentation_time) {
+                                skipped_frames += 1;
                                 continue;
                             }
 
                             if frame_count == 1 || frame_count % 1000 == 0 {
-                                debug!("Decoded frame {} (skipping ratio: {})", frame_count, skip_ratio);
+                                debug!("Decoded frame {} ({} skipped to reach {} fps)", frame_count, skipped_frames, target_fps);
                             }
 
                             // Check if frame is in hardware format
                             let frame_format = decoded.format();
-                            let is_hw_frame = matches!(frame_format,
-                                ffmpeg::format::Pixel::VAAPI |
-                                ffmpeg::format::Pixel::CUDA |
-                                ffmpeg::format::Pixel::VDPAU |
-                                ffmpeg::format::Pixel::QSV |
-                                ffmpeg::format::Pixel::VIDEOTOOLBOX |
-                                ffmpeg::format::Pixel::D3D11
-                            );
+                            let is_hw_frame = hw_decoder.is_hw_frame(&decoded);
+
+                            // 零拷贝：把 VAAPI 帧导出为 DMA-BUF 直接交给 compositor；有画面效果时需要在内存中处理
+                            if frame_format == ffmpeg::format::Pixel::VAAPI
+                                && !dmabuf_export_failed
+                                && !hints.dmabuf_formats.is_empty()
+                                && !video_filter.is_active()
+                            {
+                                match export_dmabuf(&decoded, &hints.dmabuf_formats) {
+                                    Ok(dmabuf) => {
+                                        let attributes = dmabuf.attributes();
+                                        let mut frame_data = FrameData::new(Vec::new(), attributes.width, attributes.height, hints.pixel_format);
+                                        frame_data.dmabuf = Some(dmabuf);
+                                        frame_data.pts = presentation_time;
+                                        frame_data.generation = generation;
+                                        if tx.send(frame_data).await.is_err() {
+                                            warn!("Render thread disconnected");
+                                            break Err(anyhow::anyhow!("Render thread disconnected"));
+                                        }
+                                        continue;
+                                    }
+                                    Err(e) => {
+                                        // 导出失败时本次播放不再尝试，回退到 SHM
+                                        warn!("DMA-BUF export unavailable, falling back to SHM: {}", e);
+                                        dmabuf_export_failed = true;
+                                    }
+                                }
+                            }
 
 let bgra_frame = if is_hw_frame {
-                                // 传输硬件帧到软件帧
-                                let mut sw_frame = Video::empty();
-                                hw_decoder.transfer_frame(&decoded, &mut sw_frame)?;
-                                sw_frame
+                                let gpu_target = scale_width.zip(scale_height)
+                                    .filter(|&size| size != (decoded.width(), decoded.height()));
+                                let gpu_scaled = match (&mut gpu_scaler, gpu_target) {
+                                    (Some(gpu), Some((w, h))) => match gpu.run(&decoded, gpu_download_format(hints.pixel_format), w, h) {
+                                        Ok(frame) => Some(frame),
+                                        Err(e) => {
+                                            warn!("GPU scaling unavailable, falling back to CPU scaling: {}", e);
+                                            gpu_scaler = None;
+                                            None
+                                        }
+                                    },
+                                    _ => None,
+                                };
+                                match gpu_scaled {
+                                    Some(frame) => frame,
+                                    None => {
+                                        // 传输硬件帧到软件帧
+                                        let mut sw_frame = Video::empty();
+                                        hw_decoder.transfer_frame(&decoded, &mut sw_frame)?;
+                                        sw_frame
+                                    }
+                                }
                             } else {
                                 // 已经是软件帧，直接使用
                                 decoded
                             };
 
-// 转换为 BGRA 格式（如果还不是），并应用分辨率缩放
-                            let mut bgra_frame_converted = Video::empty();
+                            // 应用画面效果；filter 暂时没有输出（例如需要更多输入帧）时跳过这一帧
+                            let Some(bgra_frame) = video_filter.run(bgra_frame) else {
+                                continue;
+                            };
+
+                            // 转换为 SHM buffer 的像素格式（如果还不是），并应用分辨率缩放
+                            let output_format = hints.pixel_format;
+                            let output_pixel = sws_pixel_format(output_format);
                             let target_width = scale_width.unwrap_or(bgra_frame.width());
                             let target_height = scale_height.unwrap_or(bgra_frame.height());
-                            
-                            if bgra_frame.format() != ffmpeg::format::Pixel::BGRA {
-                                let sw_format = bgra_frame.format();
-                                let sw_width = bgra_frame.width();
-                                let sw_height = bgra_frame.height();
-                                
+
+                            let bgra_frame_converted = if bgra_frame.format() != output_pixel
+                                || bgra_frame.width() != target_width
+                                || bgra_frame.height() != target_height
+                            {
                                 if !first_decoded {
-                                    debug!("Converting from {:?} to BGRA", sw_format);
+                                    debug!("Converting from {:?} to {:?}", bgra_frame.format(), output_pixel);
                                     first_decoded = true;
                                 }
-                                
-                                let mut converter = Context::get(
-                                    sw_format,
-                                    sw_width,
-                                    sw_height,
-                                    ffmpeg::format::Pixel::BGRA,
-                                    target_width,
-                                    target_height,
-                                    Flags::FAST_BILINEAR,
-                                ).map_err(|e| anyhow::anyhow!("Failed to create format converter: {}", e))?;
-                                converter.run(&bgra_frame, &mut bgra_frame_converted)
-                                    .map_err(|e| anyhow::anyhow!("Failed to convert frame format: {}", e))?;
+                                scaler.run(&bgra_frame, output_pixel, target_width, target_height)?
                             } else {
-                                // 已经是 BGRA 格式，但需要缩放
-                                if bgra_frame.width() != target_width || bgra_frame.height() != target_height {
-                                    let mut converter = Context::get(
-                                        ffmpeg::format::Pixel::BGRA,
-                                        bgra_frame.width(),
-                                        bgra_frame.height(),
-                                        ffmpeg::format::Pixel::BGRA,
-                                        target_width,
-                                        target_height,
-                                        Flags::FAST_BILINEAR,
-                                    ).map_err(|e| anyhow::anyhow!("Failed to create scaler: {}", e))?;
-                                    converter.run(&bgra_frame, &mut bgra_frame_converted)
-                                        .map_err(|e| anyhow::anyhow!("Failed to scale frame: {}", e))?;
-                                } else {
-                                    bgra_frame_converted = bgra_frame;
-                                }
-                            }
+                                bgra_frame
+                            };
 
                             // 使用缩放后的尺寸
                             let frame_width = bgra_frame_converted.width();
                             let frame_height = bgra_frame_converted.height();
-                            let frame_data = extract_frame_data(&bgra_frame_converted, frame_width, frame_height)?;
+                            let frame_data = extract_frame_data(&bgra_frame_converted, frame_width, frame_height, output_format.bytes_per_pixel())?;
 
                             if frame_count % 60 == 0 {
                                 info!("Frame {} - {}x{} - Hardware: {}",
                                       frame_count, frame_width, frame_height, is_hw_frame);
                             }
 
-                            if let Some(last) = last_pts {
-                                let pts_diff = (pts - last) as f64;
-                                let time_ms = (pts_diff * time_base.numerator() as f64 / time_base.denominator() as f64 * 1000.0) as u32;
-                                if time_ms > 0 && time_ms < 1000 {
-                                    frame_time_ms = time_ms;
-                                }
-                            }
-                            last_pts = Some(pts);
-
-                            let frame_data = FrameData {
-                                frame: frame_data,
-                                width: frame_width,
-                                height: frame_height,
-                                frame_time: frame_time_ms,
-                            };
+                            let mut frame_data = FrameData::new(frame_data, frame_width, frame_height, output_format);
+                            frame_data.pts = presentation_time;
+                            frame_data.generation = generation;
 
                             if tx.send(frame_data).await.is_err() {
                                 warn!("Render thread disconnected");
@@ -830,7 +542,7 @@ let bgra_frame = if is_hw_frame {
                             }
 
                             if frame_count % 60 == 0 {
-                                info!("Decoded {} frames, frame time: {}ms", frame_count, frame_time_ms);
+                                info!("Decoded {} frames, {} skipped", frame_count, skipped_frames);
                             }
                         }
                         Err(ffmpeg::Error::Eof) | Err(ffmpeg::Error::Other { errno: 11, .. }) => {
@@ -849,18 +561,86 @@ let bgra_frame = if is_hw_frame {
     }).await.map_err(|e| anyhow::anyhow!("Spawn blocking task failed: {}", e))?
 }
 
+/// 导出 DMA-BUF，并检查 compositor 是否支持该格式和 modifier
+fn export_dmabuf(frame: &Video, formats: &[DmabufFormat]) -> Result<DmabufFrame> {
+    let dmabuf = DmabufFrame::export(frame)?;
+    let attributes = dmabuf.attributes();
+    let format = DmabufFormat { format: attributes.format, modifier: attributes.modifier };
+    if !formats.contains(&format) {
+        return Err(anyhow::anyhow!(
+            "compositor does not accept format 0x{:08x} with modifier 0x{:016x}",
+            format.format, format.modifier
+        ));
+    }
+    Ok(dmabuf)
+}
+
+/// 计算缩放后的尺寸，保持宽高比，不放大
+/// `target` 为输出的物理像素尺寸，`max_width`/`max_height` 为 0 表示不限制，
+/// `cover` 为 true 时缩放到刚好覆盖目标区域（用于 fill 模式）
+fn scaled_size(
+    orig_width: u32,
+    orig_height: u32,
+    target: Option<(u32, u32)>,
+    max_width: u32,
+    max_height: u32,
+    cover: bool,
+) -> Option<(u32, u32)> {
+    let limit = (max_width > 0 && max_height > 0).then_some((max_width, max_height));
+    let (box_width, box_height) = match (target, limit) {
+        (Some((tw, th)), Some((mw, mh))) => (tw.min(mw), th.min(mh)),
+        (Some(size), None) | (None, Some(size)) => size,
+        (None, None) => return None,
+    };
+    if orig_width == 0 || orig_height == 0 || box_width == 0 || box_height == 0 {
+        return None;
+    }
+
+    // 计算缩放比例，保持宽高比
+    let scale_x = box_width as f32 / orig_width as f32;
+    let scale_y = box_height as f32 / orig_height as f32;
+    let scale = if cover { scale_x.max(scale_y) } else { scale_x.min(scale_y) };
+    let scale = scale.min(1.0);  // 不放大，只缩小
+
+    let new_width = ((orig_width as f32 * scale) as u32).max(1);
+    let new_height = ((orig_height as f32 * scale) as u32).max(1);
+
+    info!("Resolution scaling: {}x{} -> {}x{} (scale: {:.2})",
+          orig_width, orig_height, new_width, new_height, scale);
+
+    Some((new_width, new_height))
+}
+
+/// SHM 像素格式对应的 ffmpeg 像素格式（内存布局相同）
+/// GPU 缩放后下载的格式，10 位输出保留 10 位精度
+fn gpu_download_format(format: PixelFormat) -> ffmpeg::format::Pixel {
+    match format {
+        PixelFormat::Xrgb2101010 => ffmpeg::format::Pixel::P010LE,
+        _ => ffmpeg::format::Pixel::NV12,
+    }
+}
+
+fn sws_pixel_format(format: PixelFormat) -> ffmpeg::format::Pixel {
+    match format {
+        PixelFormat::Argb8888 | PixelFormat::Xrgb8888 => ffmpeg::format::Pixel::BGRA,
+        PixelFormat::Xrgb2101010 => ffmpeg::format::Pixel::X2RGB10LE,
+        PixelFormat::Rgb565 => ffmpeg::format::Pixel::RGB565LE,
+    }
+}
+
 /// Extract frame data from Video frame (optimized with zero-copy when possible)
 fn extract_frame_data(
     frame: &ffmpeg::util::frame::video::Video,
     width: u32,
     height: u32,
+    bytes_per_pixel: u32,
 ) -> Result<Vec<u8>> {
     let stride = frame.stride(0);
     let data = frame.data(0);
 
     let width = width as usize;
     let height = height as usize;
-    let row_size = width * 4;
+    let row_size = width * bytes_per_pixel as usize;
     let mut frame_data = vec![0u8; row_size * height];
 
     unsafe {
@@ -884,25 +664,32 @@ fn extract_frame_data(
 }
 
 async fn render_frames_async(
-    mut rx: mpsc::Receiver<FrameData>,
+    mut rx: mpsc::Receiver<Arc<FrameData>>,
+    hints_tx: watch::Sender<RenderHints>,
+    link: SharedLink,
     is_paused: Arc<Mutex<bool>>,
     is_stopped: Arc<Mutex<bool>>,
+    output_target: OutputTarget,
+    claimed_outputs: ClaimedOutputs,
+    surface_options: SurfaceOptions,
+    connection_state: Arc<std::sync::Mutex<ConnectionState>>,
 ) {
-    info!("Render thread started");
+    info!("Render thread started ({}, scaling: {})", output_target, surface_options.scaling);
 
-    let mut wayland_app = match crate::wayland::WaylandApp::new() {
-        Ok(app) => app,
-        Err(e) => {
-            error!("Failed to initialize Wayland: {}", e);
-            return;
-        }
+    let Some(mut wayland_app) = connect_wayland(&output_target, &claimed_outputs, &surface_options, &connection_state, &is_stopped).await else {
+        return;
     };
 
     let mut frame_count = 0u64;
     let start_time = std::time::Instant::now();
     let mut first_frame_time: Option<std::time::Instant> = None;
-    let mut next_frame_time = start_time;
+    let mut playback_clock = PlaybackClock::new();
+    let mut dropped_frames = 0u64;
     let mut last_frame_time: Option<std::time::Instant> = None;
+    let mut waiting_for_output = false;
+    // compositor 拒绝过 DMA-BUF 后（包括重连之后）只使用 SHM
+    let mut dmabuf_disabled = false;
+    update_render_hints(&wayland_app, &hints_tx, surface_options.scaling, dmabuf_disabled);
 
     while !*is_stopped.lock().await {
         if *is_paused.lock().await {
@@ -911,18 +698,59 @@ async fn render_frames_async(
             continue;
         }
 
+        // compositor 崩溃或重启：丢弃旧连接并重连，期间不取帧，
+        // 解码任务阻塞在 send 上，因此重连后从断开时的位置继续播放
+        if wayland_app.is_connection_lost() {
+            warn!("Wayland connection lost, reconnecting");
+            if wayland_app.dmabuf_import_failed() {
+                dmabuf_disabled = true;
+            }
+            let Some(app) = connect_wayland(&output_target, &claimed_outputs, &surface_options, &connection_state, &is_stopped).await else {
+                break;
+            };
+            wayland_app = app;
+            first_frame_time = None;
+            playback_clock.reset();
+            update_render_hints(&wayland_app, &hints_tx, surface_options.scaling, dmabuf_disabled);
+            continue;
+        }
+
+        // 没有可显示的输出（例如显示器已拔出）时不再取帧：
+        // 帧通道写满后解码任务会阻塞在 send 上，不再消耗 CPU，直到输出重新接入
+        if !wayland_app.has_surfaces() {
+            if !waiting_for_output {
+                info!("No output available, suspending playback until an output is connected");
+                waiting_for_output = true;
+            }
+            if let Err(e) = wayland_app.dispatch_events() {
+                error!("Failed to dispatch Wayland events: {}", e);
+            }
+            handle_wayland_events(&mut wayland_app);
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            continue;
+        }
+        if waiting_for_output {
+            info!("Output connected, resuming playback");
+            waiting_for_output = false;
+            playback_clock.reset();
+        }
+
         // 使用阻塞 recv() 而不是 timeout，减少轮询
         match rx.recv().await {
             Some(frame_data) => {
+                // 按 PTS 安排显示时间，错过太多的帧直接丢弃
+                let present_at = match playback_clock.schedule(frame_data.generation, frame_data.pts, std::time::Instant::now()) {
+                    FrameSchedule::Present(at) => at,
+                    FrameSchedule::Drop => {
+                        dropped_frames += 1;
+                        if dropped_frames % 60 == 1 {
+                            warn!("Dropped late frame at {:?} ({} dropped so far)", frame_data.pts, dropped_frames);
+                        }
+                        continue;
+                    }
+                };
                 frame_count += 1;
 
-                if frame_data.frame_time == 33 && frame_count > 100 {
-                    frame_count = 0;
-                    first_frame_time = Some(std::time::Instant::now());
-                    next_frame_time = std::time::Instant::now();
-                    info!("Loop detected, resetting frame count and timing");
-                }
-
                 if let Some(last) = last_frame_time {
                     let gap = last.elapsed();
                     if gap.as_millis() > 50 {
@@ -935,25 +763,61 @@ async fn render_frames_async(
                 }
                 last_frame_time = Some(std::time::Instant::now());
 
-                let now = std::time::Instant::now();
                 if first_frame_time.is_none() {
-                    first_frame_time = Some(now);
-                    next_frame_time = now;
+                    first_frame_time = Some(std::time::Instant::now());
                     info!("First frame received, starting playback");
                 }
 
+                // 等到该帧的显示时间，期间持续处理 Wayland 事件；
+                // 提前半个刷新周期提交，让 commit 落在离显示时间最近的 vblank 上
+                let commit_at = present_at
+                    .checked_sub(wayland_app.refresh_interval() / 2)
+                    .unwrap_or(present_at);
+                if let Err(e) = wayland_app.wait_until(commit_at).await {
+                    error!("Failed to dispatch Wayland events: {}", e);
+                }
+
+                // 等待 frame callback：输出被遮挡或关闭时 compositor 不再回调，
+                // 渲染随之停止，解码任务也会因帧通道写满而阻塞
+                let mut ready = false;
+                while !ready && wayland_app.has_surfaces() && !wayland_app.is_connection_lost() {
+                    match wayland_app.wait_ready(std::time::Instant::now() + Duration::from_secs(1)).await {
+                        Ok(r) => ready = r,
+                        Err(e) => {
+                            error!("Failed to dispatch Wayland events: {}", e);
+                            break;
+                        }
+                    }
+                    if *is_stopped.lock().await {
+                        break;
+                    }
+                }
+
                 let render_start = std::time::Instant::now();
 
-                if let Err(e) =
-                    wayland_app.render_frame(&frame_data.frame, frame_data.width, frame_data.height)
-                {
-                    error!("Failed to render frame: {}", e);
-                } else {
-                    // 每帧都 dispatch 以保持流畅
-                    if let Err(e) = wayland_app.dispatch_events() {
-                        error!("Failed to dispatch Wayland events: {}", e);
+                if ready {
+                    let result = match frame_data.dmabuf {
+                        Some(ref dmabuf) => {
+                            // 帧可能同时显示在其他输出上，引用计数保持到 compositor release
+                            let attributes = dmabuf.attributes().clone();
+                            wayland_app.render_frame_dmabuf(&attributes, Box::new(frame_data.clone())).inspect_err(|_| {
+                                // 之后的帧回退到 SHM
+                                dmabuf_disabled = true;
+                            })
+                        }
+                        None => wayland_app.render_frame(&frame_data.frame, frame_data.width, frame_data.height, frame_data.format),
+                    };
+                    match result {
+                        Ok(()) => link.lock().unwrap().clock.update(frame_data.pts),
+                        Err(e) => error!("Failed to render frame: {}", e),
                     }
                 }
+                handle_wayland_events(&mut wayland_app);
+                if wayland_app.dmabuf_import_failed() && !dmabuf_disabled {
+                    warn!("Compositor rejected DMA-BUF, falling back to SHM");
+                    dmabuf_disabled = true;
+                }
+                update_render_hints(&wayland_app, &hints_tx, surface_options.scaling, dmabuf_disabled);
 
                 let render_time = render_start.elapsed();
 
@@ -971,24 +835,17 @@ async fn render_frames_async(
                 if frame_count % 60 == 0 {
                     let total_elapsed = start_time.elapsed();
                     info!(
-                        "Render {}: {}x{}, frame_time={}ms, render_time={:.2}ms, total_elapsed={:.2}s, FPS={:.2}",
+                        "Render {}: {}x{}, pts={:.3}s, render_time={:.2}ms, total_elapsed={:.2}s, FPS={:.2}, dropped={}",
                         frame_count,
                         frame_data.width,
                         frame_data.height,
-                        frame_data.frame_time,
+                        frame_data.pts.as_secs_f64(),
                         render_time.as_secs_f64() * 1000.0,
                         total_elapsed.as_secs_f64(),
-                        fps
+                        fps,
+                        dropped_frames
                     );
                 }
-
-                next_frame_time += Duration::from_millis(frame_data.frame_time as u64);
-                let now = std::time::Instant::now();
-
-                if now < next_frame_time {
-                    let sleep_time = next_frame_time.duration_since(now);
-                    tokio::time::sleep(sleep_time).await;
-                }
             }
             None => {
                 info!("Decode thread disconnected");
@@ -1001,4 +858,104 @@ async fn render_frames_async(
         "Render thread stopped, total frames rendered: {}",
         frame_count
     );
-}
\ No newline at end of file
+}
+
+/// 连接 compositor，失败时按指数退避重试，直到成功或壁纸被停止
+async fn connect_wayland(
+    output_target: &OutputTarget,
+    claimed_outputs: &ClaimedOutputs,
+    surface_options: &SurfaceOptions,
+    connection_state: &Arc<std::sync::Mutex<ConnectionState>>,
+    is_stopped: &Arc<Mutex<bool>>,
+) -> Option<WaylandApp> {
+    const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    let reconnecting = *connection_state.lock().unwrap() != ConnectionState::Connecting;
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 0u32;
+
+    loop {
+        if *is_stopped.lock().await {
+            return None;
+        }
+
+        attempt += 1;
+        if reconnecting {
+            *connection_state.lock().unwrap() = ConnectionState::Reconnecting { attempt };
+        }
+
+        match WaylandApp::new(output_target.clone(), claimed_outputs.clone(), surface_options.clone()) {
+            Ok(app) => {
+                if reconnecting {
+                    info!("Reconnected to Wayland after {} attempt(s)", attempt);
+                }
+                *connection_state.lock().unwrap() = ConnectionState::Connected;
+                return Some(app);
+            }
+            Err(e) => {
+                error!("Failed to connect to Wayland (attempt {}): {}, retrying in {:?}", attempt, e, backoff);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+/// 把输出的物理像素尺寸和 DMA-BUF 支持情况同步给解码任务，只在变化时通知
+fn update_render_hints(
+    wayland_app: &WaylandApp,
+    hints_tx: &watch::Sender<RenderHints>,
+    scaling: ScalingMode,
+    dmabuf_disabled: bool,
+) {
+    let target = wayland_app.target_size();
+    // fit/center/tile 需要在 CPU 上合成，不能直接显示解码器的 buffer
+    let dmabuf_usable = !dmabuf_disabled && matches!(scaling, ScalingMode::Fill | ScalingMode::Stretch | ScalingMode::Span);
+    let pixel_format = wayland_app.pixel_format();
+    hints_tx.send_if_modified(|hints| {
+        let mut changed = false;
+        if hints.pixel_format != pixel_format {
+            hints.pixel_format = pixel_format;
+            changed = true;
+        }
+        if target.is_some() && hints.target_size != target {
+            hints.target_size = target;
+            changed = true;
+        }
+        let formats = if dmabuf_usable { wayland_app.dmabuf_formats() } else { &[] };
+        if hints.dmabuf_formats != formats {
+            hints.dmabuf_formats = formats.to_vec();
+            changed = true;
+        }
+        changed
+    });
+}
+
+/// 处理 WaylandApp 上报的事件
+fn handle_wayland_events(wayland_app: &mut WaylandApp) {
+    for event in wayland_app.take_events() {
+        match event {
+            WaylandEvent::SurfaceClosed { output_id } => {
+                // 销毁 surface，等输出重新配置或重新接入时再创建
+                wayland_app.recreate_surface(output_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scaled_size_follows_output() {
+        // 缩放为 2 的 2560x1600 屏幕按物理像素解码，不被限制在 720p
+        assert_eq!(scaled_size(5120, 3200, Some((2560, 1600)), 0, 0, true), Some((2560, 1600)));
+        assert_eq!(scaled_size(5120, 3200, Some((2560, 1600)), 0, 0, false), Some((2560, 1600)));
+        // 视频比输出小时不放大
+        assert_eq!(scaled_size(1920, 1080, Some((2560, 1600)), 0, 0, true), Some((1920, 1080)));
+        // 还不知道输出尺寸时保持原始尺寸
+        assert_eq!(scaled_size(1920, 1080, None, 0, 0, true), None);
+    }
+}
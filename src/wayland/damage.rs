@@ -0,0 +1,112 @@
+/// 比较时使用的块大小（像素）
+const TILE_SIZE: u32 = 64;
+/// 矩形过多时合并为一个包围盒，避免 compositor 处理大量小矩形
+const MAX_RECTS: usize = 16;
+
+/// buffer 坐标下的损坏区域
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DamageRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl DamageRect {
+    pub fn full(width: u32, height: u32) -> Self {
+        Self { x: 0, y: 0, width: width as i32, height: height as i32 }
+    }
+
+    fn union(self, other: DamageRect) -> DamageRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        DamageRect { x, y, width: right - x, height: bottom - y }
+    }
+}
+
+/// 按块比较两帧，返回内容发生变化的区域，内容相同时为空
+/// `old` 和 `new` 的尺寸、行跨度和像素格式必须相同
+pub fn diff_rects(old: &[u8], new: &[u8], width: u32, height: u32, stride: u32, bpp: u32) -> Vec<DamageRect> {
+    let mut rects: Vec<DamageRect> = Vec::new();
+    // 上一行块中变化的连续区间，与当前行完全对齐时向下合并
+    let mut previous_row: Vec<usize> = Vec::new();
+
+    for tile_y in (0..height).step_by(TILE_SIZE as usize) {
+        let tile_height = TILE_SIZE.min(height - tile_y);
+        let mut current_row = Vec::new();
+        let mut run: Option<DamageRect> = None;
+
+        for tile_x in (0..width).step_by(TILE_SIZE as usize) {
+            let tile_width = TILE_SIZE.min(width - tile_x);
+            let changed = (tile_y..tile_y + tile_height).any(|y| {
+                let start = (y * stride + tile_x * bpp) as usize;
+                let end = start + (tile_width * bpp) as usize;
+                old[start..end] != new[start..end]
+            });
+            if changed {
+                let tile = DamageRect {
+                    x: tile_x as i32,
+                    y: tile_y as i32,
+                    width: tile_width as i32,
+                    height: tile_height as i32,
+                };
+                run = Some(run.map_or(tile, |r| r.union(tile)));
+            } else if let Some(r) = run.take() {
+                current_row.push(push_merged(&mut rects, &previous_row, r));
+            }
+        }
+        if let Some(r) = run.take() {
+            current_row.push(push_merged(&mut rects, &previous_row, r));
+        }
+        previous_row = current_row;
+    }
+
+    if rects.len() > MAX_RECTS {
+        let bounds = rects.iter().copied().reduce(DamageRect::union).unwrap();
+        return vec![bounds];
+    }
+    rects
+}
+
+/// 如果上一行有横向范围相同的矩形就向下延伸它，否则新增矩形；返回矩形的索引
+fn push_merged(rects: &mut Vec<DamageRect>, previous_row: &[usize], rect: DamageRect) -> usize {
+    for &index in previous_row {
+        let above = &mut rects[index];
+        if above.x == rect.x && above.width == rect.width && above.y + above.height == rect.y {
+            above.height += rect.height;
+            return index;
+        }
+    }
+    rects.push(rect);
+    rects.len() - 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_identical_frames_have_no_damage() {
+        let frame = vec![7u8; 200 * 100 * 4];
+        assert!(diff_rects(&frame, &frame, 200, 100, 200 * 4, 4).is_empty());
+    }
+
+    #[test]
+    fn test_single_pixel_damages_one_tile() {
+        let old = vec![0u8; 200 * 100 * 4];
+        let mut new = old.clone();
+        new[(70 * 200 + 150) * 4] = 1;
+        let rects = diff_rects(&old, &new, 200, 100, 200 * 4, 4);
+        assert_eq!(rects, vec![DamageRect { x: 128, y: 64, width: 64, height: 36 }]);
+    }
+
+    #[test]
+    fn test_full_change_merges_into_one_rect() {
+        let old = vec![0u8; 300 * 200 * 2];
+        let new = vec![1u8; 300 * 200 * 2];
+        let rects = diff_rects(&old, &new, 300, 200, 300 * 2, 2);
+        assert_eq!(rects, vec![DamageRect::full(300, 200)]);
+    }
+}
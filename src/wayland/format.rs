@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use wayland_client::protocol::wl_shm;
+
+use super::layout::Color;
+
+/// SHM buffer 的像素格式（内存布局均为小端）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PixelFormat {
+    /// B, G, R, A
+    Argb8888,
+    /// B, G, R, X，不透明，compositor 无需混合
+    #[default]
+    Xrgb8888,
+    /// 每通道 10 位，不透明
+    Xrgb2101010,
+    /// 每像素 2 字节，节省内存和带宽
+    Rgb565,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> u32 {
+        match self {
+            PixelFormat::Rgb565 => 2,
+            _ => 4,
+        }
+    }
+
+    pub fn wl_format(self) -> wl_shm::Format {
+        match self {
+            PixelFormat::Argb8888 => wl_shm::Format::Argb8888,
+            PixelFormat::Xrgb8888 => wl_shm::Format::Xrgb8888,
+            PixelFormat::Xrgb2101010 => wl_shm::Format::Xrgb2101010,
+            PixelFormat::Rgb565 => wl_shm::Format::Rgb565,
+        }
+    }
+
+    /// 格式不含 alpha 通道，可以声明为不透明区域
+    pub fn is_opaque(self) -> bool {
+        self != PixelFormat::Argb8888
+    }
+
+    /// 以该格式编码一个像素
+    pub fn encode(self, color: Color) -> Vec<u8> {
+        match self {
+            PixelFormat::Argb8888 | PixelFormat::Xrgb8888 => vec![color.b, color.g, color.r, 0xff],
+            PixelFormat::Xrgb2101010 => {
+                // 8 位扩展到 10 位
+                let expand = |c: u8| ((c as u32) << 2) | ((c as u32) >> 6);
+                let pixel = (0b11 << 30) | (expand(color.r) << 20) | (expand(color.g) << 10) | expand(color.b);
+                pixel.to_le_bytes().to_vec()
+            }
+            PixelFormat::Rgb565 => {
+                let pixel = ((color.r as u16 >> 3) << 11) | ((color.g as u16 >> 2) << 5) | (color.b as u16 >> 3);
+                pixel.to_le_bytes().to_vec()
+            }
+        }
+    }
+}
+
+/// 画质与内存占用的取舍，决定 SHM buffer 使用的像素格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorQuality {
+    /// RGB565，内存占用减半
+    Low,
+    /// XRGB8888
+    #[default]
+    Normal,
+    /// XRGB2101010，保留 10 位视频的精度
+    High,
+}
+
+impl ColorQuality {
+    /// 按优先级排列的候选格式，Argb8888 是所有 compositor 都必须支持的格式
+    pub fn preferred_formats(self) -> &'static [PixelFormat] {
+        match self {
+            ColorQuality::Low => &[PixelFormat::Rgb565, PixelFormat::Xrgb8888, PixelFormat::Argb8888],
+            ColorQuality::Normal => &[PixelFormat::Xrgb8888, PixelFormat::Argb8888],
+            ColorQuality::High => &[PixelFormat::Xrgb2101010, PixelFormat::Xrgb8888, PixelFormat::Argb8888],
+        }
+    }
+}
+
+impl FromStr for ColorQuality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "low" => Ok(ColorQuality::Low),
+            "normal" => Ok(ColorQuality::Normal),
+            "high" => Ok(ColorQuality::High),
+            _ => Err(format!("unknown quality: {} (expected low, normal or high)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ColorQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ColorQuality::Low => "low",
+            ColorQuality::Normal => "normal",
+            ColorQuality::High => "high",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+
+/// layer surface 所在的层
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layer {
+    /// 壁纸层，位于所有窗口之下
+    #[default]
+    Background,
+    /// 位于窗口之下、壁纸之上
+    Bottom,
+    /// 位于窗口之上
+    Top,
+    /// 位于所有内容（包括全屏窗口）之上，配合 opacity 做桌面挂件
+    Overlay,
+}
+
+impl Layer {
+    pub fn wl_layer(self) -> zwlr_layer_shell_v1::Layer {
+        match self {
+            Layer::Background => zwlr_layer_shell_v1::Layer::Background,
+            Layer::Bottom => zwlr_layer_shell_v1::Layer::Bottom,
+            Layer::Top => zwlr_layer_shell_v1::Layer::Top,
+            Layer::Overlay => zwlr_layer_shell_v1::Layer::Overlay,
+        }
+    }
+}
+
+impl FromStr for Layer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "background" => Ok(Layer::Background),
+            "bottom" => Ok(Layer::Bottom),
+            "top" => Ok(Layer::Top),
+            "overlay" => Ok(Layer::Overlay),
+            _ => Err(format!("unknown layer: {} (expected background, bottom, top or overlay)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Layer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Layer::Background => "background",
+            Layer::Bottom => "bottom",
+            Layer::Top => "top",
+            Layer::Overlay => "overlay",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// surface 锚定的边，格式为 "top,bottom,left,right" 的任意组合或 "all"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Anchor {
+    pub top: bool,
+    pub bottom: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl Default for Anchor {
+    fn default() -> Self {
+        Self { top: true, bottom: true, left: true, right: true }
+    }
+}
+
+impl Anchor {
+    pub fn wl_anchor(self) -> zwlr_layer_surface_v1::Anchor {
+        let mut anchor = zwlr_layer_surface_v1::Anchor::empty();
+        if self.top {
+            anchor |= zwlr_layer_surface_v1::Anchor::Top;
+        }
+        if self.bottom {
+            anchor |= zwlr_layer_surface_v1::Anchor::Bottom;
+        }
+        if self.left {
+            anchor |= zwlr_layer_surface_v1::Anchor::Left;
+        }
+        if self.right {
+            anchor |= zwlr_layer_surface_v1::Anchor::Right;
+        }
+        anchor
+    }
+
+    /// 横向和纵向是否都锚定了两边，此时尺寸由 compositor 决定
+    pub fn is_stretched(self) -> (bool, bool) {
+        (self.left && self.right, self.top && self.bottom)
+    }
+}
+
+impl FromStr for Anchor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(Anchor::default());
+        }
+        let mut anchor = Anchor { top: false, bottom: false, left: false, right: false };
+        for edge in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match edge.to_lowercase().as_str() {
+                "top" => anchor.top = true,
+                "bottom" => anchor.bottom = true,
+                "left" => anchor.left = true,
+                "right" => anchor.right = true,
+                _ => return Err(format!("unknown anchor edge: {} (expected top, bottom, left or right)", edge)),
+            }
+        }
+        Ok(anchor)
+    }
+}
+
+impl TryFrom<String> for Anchor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Anchor> for String {
+    fn from(anchor: Anchor) -> Self {
+        anchor.to_string()
+    }
+}
+
+impl std::fmt::Display for Anchor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if *self == Anchor::default() {
+            return write!(f, "all");
+        }
+        let edges: Vec<&str> = [("top", self.top), ("bottom", self.bottom), ("left", self.left), ("right", self.right)]
+            .iter()
+            .filter(|(_, set)| *set)
+            .map(|(name, _)| *name)
+            .collect();
+        write!(f, "{}", edges.join(","))
+    }
+}
+
+/// 到锚定边的距离（逻辑像素），用于补偿显示器边框
+/// 格式同 CSS："all"、"vertical,horizontal" 或 "top,right,bottom,left"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Margins {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+impl FromStr for Margins {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<i32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("invalid margins: {} (expected 1, 2 or 4 comma-separated integers)", s))?;
+        match values.as_slice() {
+            [all] => Ok(Margins { top: *all, right: *all, bottom: *all, left: *all }),
+            [vertical, horizontal] => Ok(Margins { top: *vertical, right: *horizontal, bottom: *vertical, left: *horizontal }),
+            [top, right, bottom, left] => Ok(Margins { top: *top, right: *right, bottom: *bottom, left: *left }),
+            _ => Err(format!("invalid margins: {} (expected 1, 2 or 4 comma-separated integers)", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Margins {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Margins> for String {
+    fn from(margins: Margins) -> Self {
+        margins.to_string()
+    }
+}
+
+impl std::fmt::Display for Margins {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{},{}", self.top, self.right, self.bottom, self.left)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_anchor_and_margins() {
+        let anchor: Anchor = "top, left".parse().unwrap();
+        assert_eq!(anchor, Anchor { top: true, bottom: false, left: true, right: false });
+        assert_eq!(anchor.is_stretched(), (false, false));
+        assert_eq!("all".parse::<Anchor>().unwrap().to_string(), "all");
+        assert!("middle".parse::<Anchor>().is_err());
+
+        assert_eq!("10,20".parse::<Margins>(), Ok(Margins { top: 10, right: 20, bottom: 10, left: 20 }));
+        assert_eq!("5".parse::<Margins>().unwrap().to_string(), "5,5,5,5");
+        assert!("1,2,3".parse::<Margins>().is_err());
+    }
+}
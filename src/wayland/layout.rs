@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// 视频帧在输出上的缩放方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScalingMode {
+    /// 保持宽高比铺满输出，超出部分裁剪
+    #[default]
+    #[serde(alias = "crop")]
+    Fill,
+    /// 保持宽高比完整显示，空白处用背景色填充
+    #[serde(alias = "letterbox")]
+    Fit,
+    /// 拉伸到输出尺寸，不保持宽高比
+    Stretch,
+    /// 原始尺寸居中显示
+    Center,
+    /// 原始尺寸从左上角开始平铺
+    Tile,
+    /// 一个视频按 fill 方式横跨所有输出，由 WaylandApp 按输出位置计算布局
+    Span,
+}
+
+impl FromStr for ScalingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fill" | "crop" => Ok(ScalingMode::Fill),
+            "fit" | "letterbox" => Ok(ScalingMode::Fit),
+            "stretch" => Ok(ScalingMode::Stretch),
+            "center" => Ok(ScalingMode::Center),
+            "tile" => Ok(ScalingMode::Tile),
+            "span" => Ok(ScalingMode::Span),
+            _ => Err(format!(
+                "unknown scaling mode: {} (expected fill, fit, stretch, center, tile or span)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ScalingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ScalingMode::Fill => "fill",
+            ScalingMode::Fit => "fit",
+            ScalingMode::Stretch => "stretch",
+            ScalingMode::Center => "center",
+            ScalingMode::Tile => "tile",
+            ScalingMode::Span => "span",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 解码端缩放视频帧使用的算法，越往后画质越好、CPU 占用越高
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScalingFilter {
+    #[default]
+    FastBilinear,
+    Bilinear,
+    Bicubic,
+    Lanczos,
+}
+
+impl FromStr for ScalingFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fast-bilinear" | "fast_bilinear" | "fast" => Ok(ScalingFilter::FastBilinear),
+            "bilinear" => Ok(ScalingFilter::Bilinear),
+            "bicubic" => Ok(ScalingFilter::Bicubic),
+            "lanczos" => Ok(ScalingFilter::Lanczos),
+            _ => Err(format!(
+                "unknown scaling filter: {} (expected fast-bilinear, bilinear, bicubic or lanczos)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ScalingFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ScalingFilter::FastBilinear => "fast-bilinear",
+            ScalingFilter::Bilinear => "bilinear",
+            ScalingFilter::Bicubic => "bicubic",
+            ScalingFilter::Lanczos => "lanczos",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 背景色（fit/center/tile 模式下未被视频覆盖的区域），格式为 "#rrggbb"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim_start_matches('#');
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("invalid color: {} (expected #rrggbb)", s));
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string());
+        Ok(Color {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        })
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// 一帧在某个 surface 上的布局
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    /// 提交给 compositor 的 buffer 尺寸
+    pub canvas: (u32, u32),
+    /// 帧在 buffer 中的左上角位置，可以为负（被裁剪），平铺时有多个
+    pub placements: Vec<(i32, i32)>,
+    /// viewport 源矩形 (x, y, width, height)，单位为 buffer 像素
+    pub source: (f64, f64, f64, f64),
+}
+
+impl Layout {
+    /// 帧数据可以直接作为 buffer 使用，无需合成
+    pub fn is_direct(&self, frame: (u32, u32)) -> bool {
+        self.canvas == frame && self.placements == [(0, 0)]
+    }
+}
+
+/// 计算帧在 surface 上的布局
+/// `frame` 为帧的像素尺寸，`surface` 为 surface 的物理像素尺寸
+pub fn compute_layout(mode: ScalingMode, frame: (u32, u32), surface: (u32, u32)) -> Layout {
+    let (fw, fh) = frame;
+    let (sw, sh) = surface;
+    let full = (0.0, 0.0, fw as f64, fh as f64);
+    if fw == 0 || fh == 0 || sw == 0 || sh == 0 {
+        return Layout { canvas: frame, placements: vec![(0, 0)], source: full };
+    }
+
+    // 比较宽高比：fw/fh 与 sw/sh
+    let frame_wider = fw as u64 * sh as u64 > sw as u64 * fh as u64;
+
+    match mode {
+        ScalingMode::Stretch => Layout { canvas: frame, placements: vec![(0, 0)], source: full },
+        // 单个输出上 span 等同于 fill
+        ScalingMode::Fill | ScalingMode::Span => {
+            // 裁剪出与 surface 宽高比相同的中心区域，由 viewport 放大
+            let (cw, ch) = if frame_wider {
+                (fh as f64 * sw as f64 / sh as f64, fh as f64)
+            } else {
+                (fw as f64, fw as f64 * sh as f64 / sw as f64)
+            };
+            Layout {
+                canvas: frame,
+                placements: vec![(0, 0)],
+                source: ((fw as f64 - cw) / 2.0, (fh as f64 - ch) / 2.0, cw, ch),
+            }
+        }
+        ScalingMode::Fit => {
+            // 画布与 surface 宽高比相同且刚好容纳整帧，由 viewport 缩放到 surface，
+            // 这样不需要在 CPU 上放大视频
+            let (cw, ch) = if frame_wider {
+                (fw, ((fw as u64 * sh as u64 + sw as u64 / 2) / sw as u64) as u32)
+            } else {
+                (((fh as u64 * sw as u64 + sh as u64 / 2) / sh as u64) as u32, fh)
+            };
+            let (cw, ch) = (cw.max(fw), ch.max(fh));
+            Layout {
+                canvas: (cw, ch),
+                placements: vec![((cw - fw) as i32 / 2, (ch - fh) as i32 / 2)],
+                source: (0.0, 0.0, cw as f64, ch as f64),
+            }
+        }
+        ScalingMode::Center => Layout {
+            canvas: surface,
+            placements: vec![((sw as i32 - fw as i32) / 2, (sh as i32 - fh as i32) / 2)],
+            source: (0.0, 0.0, sw as f64, sh as f64),
+        },
+        ScalingMode::Tile => {
+            let mut placements = Vec::new();
+            for y in (0..sh).step_by(fh as usize) {
+                for x in (0..sw).step_by(fw as usize) {
+                    placements.push((x as i32, y as i32));
+                }
+            }
+            Layout {
+                canvas: surface,
+                placements,
+                source: (0.0, 0.0, sw as f64, sh as f64),
+            }
+        }
+    }
+}
+
+/// 按布局把帧合成到 buffer 中，未被覆盖的区域填充背景色
+/// `background` 为按 buffer 像素格式编码后的一个像素
+pub fn compose(
+    dst: &mut [u8],
+    layout: &Layout,
+    frame: &[u8],
+    frame_size: (u32, u32),
+    background: &[u8],
+) {
+    let bpp = background.len() as i64;
+    let (cw, ch) = (layout.canvas.0 as i64, layout.canvas.1 as i64);
+    let (fw, fh) = (frame_size.0 as i64, frame_size.1 as i64);
+
+    for pixel in dst[..(cw * ch * bpp) as usize].chunks_exact_mut(bpp as usize) {
+        pixel.copy_from_slice(background);
+    }
+
+    for &(px, py) in &layout.placements {
+        let (px, py) = (px as i64, py as i64);
+        // 与画布求交，裁剪超出部分
+        let x0 = px.max(0);
+        let x1 = (px + fw).min(cw);
+        let y0 = py.max(0);
+        let y1 = (py + fh).min(ch);
+        if x0 >= x1 || y0 >= y1 {
+            continue;
+        }
+        let row_bytes = ((x1 - x0) * bpp) as usize;
+        for y in y0..y1 {
+            let src = (((y - py) * fw + (x0 - px)) * bpp) as usize;
+            let dst_offset = ((y * cw + x0) * bpp) as usize;
+            dst[dst_offset..dst_offset + row_bytes].copy_from_slice(&frame[src..src + row_bytes]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fill_crops_to_surface_aspect() {
+        // 16:9 视频显示在 21:9 超宽屏上，裁剪上下
+        let layout = compute_layout(ScalingMode::Fill, (1920, 1080), (3440, 1440));
+        assert_eq!(layout.canvas, (1920, 1080));
+        let (x, y, w, h) = layout.source;
+        assert_eq!((x, w), (0.0, 1920.0));
+        assert!((w / h - 3440.0 / 1440.0).abs() < 1e-6);
+        assert!((y * 2.0 + h - 1080.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fit_letterboxes_portrait_output() {
+        let layout = compute_layout(ScalingMode::Fit, (1920, 1080), (1080, 1920));
+        assert_eq!(layout.canvas, (1920, 3413));
+        assert_eq!(layout.placements, vec![(0, 1166)]);
+        assert!(!layout.is_direct((1920, 1080)));
+    }
+
+    #[test]
+    fn test_stretch_is_direct() {
+        let layout = compute_layout(ScalingMode::Stretch, (1280, 720), (2560, 1080));
+        assert!(layout.is_direct((1280, 720)));
+        assert_eq!(layout.source, (0.0, 0.0, 1280.0, 720.0));
+    }
+
+    #[test]
+    fn test_center_and_tile() {
+        let layout = compute_layout(ScalingMode::Center, (1280, 720), (1920, 1080));
+        assert_eq!(layout.canvas, (1920, 1080));
+        assert_eq!(layout.placements, vec![(320, 180)]);
+
+        let layout = compute_layout(ScalingMode::Tile, (800, 600), (1920, 1080));
+        assert_eq!(layout.placements.len(), 3 * 2);
+    }
+
+    #[test]
+    fn test_compose_clips_and_fills_background() {
+        let frame = vec![0xffu8; 4 * 4 * 4];
+        let layout = Layout {
+            canvas: (2, 2),
+            placements: vec![(-3, 1)],
+            source: (0.0, 0.0, 2.0, 2.0),
+        };
+        let mut dst = vec![0u8; 2 * 2 * 4];
+        compose(&mut dst, &layout, &frame, (4, 4), &[0x30, 0x20, 0x10, 0xff]);
+        assert_eq!(&dst[0..4], &[0x30, 0x20, 0x10, 0xff]);
+        assert_eq!(&dst[8..12], &[0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(&dst[12..16], &[0x30, 0x20, 0x10, 0xff]);
+    }
+
+    #[test]
+    fn test_parse_scaling_mode_and_color() {
+        assert_eq!("letterbox".parse::<ScalingMode>(), Ok(ScalingMode::Fit));
+        assert!("zoom".parse::<ScalingMode>().is_err());
+        assert_eq!("#ff8000".parse::<Color>(), Ok(Color { r: 0xff, g: 0x80, b: 0x00 }));
+        assert!("red".parse::<Color>().is_err());
+    }
+}
@@ -1,3 +1,14 @@
 pub mod wayland;
+pub mod layout;
+pub mod layer;
+pub mod format;
+pub mod damage;
+pub mod span;
 
-pub use wayland::WaylandApp;
+pub use format::{ColorQuality, PixelFormat};
+pub use layer::{Anchor, Layer, Margins};
+pub use layout::{Color, ScalingFilter, ScalingMode};
+pub use wayland::{
+    ClaimedOutputs, ConnectionState, DEFAULT_NAMESPACE, DRM_FORMAT_MOD_INVALID, DmabufAttributes, DmabufFormat,
+    DmabufPlane, OutputTarget, SurfaceOptions, WaylandApp, WaylandEvent,
+};
@@ -0,0 +1,118 @@
+/// 输出在 compositor 全局坐标系中的位置和尺寸（逻辑像素）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// 屏幕可视区域的物理宽度（毫米），未知时为 0
+    pub physical_width_mm: u32,
+    /// 每一侧的边框宽度（毫米）
+    pub bezel_mm: f64,
+}
+
+impl OutputGeometry {
+    /// 边框宽度换算为逻辑像素
+    fn bezel_px(&self) -> f64 {
+        if self.physical_width_mm == 0 {
+            return 0.0;
+        }
+        self.bezel_mm * self.width as f64 / self.physical_width_mm as f64
+    }
+
+    fn overlaps_rows(&self, other: &OutputGeometry) -> bool {
+        self.y < other.y + other.height as i32 && other.y < self.y + self.height as i32
+    }
+
+    fn overlaps_columns(&self, other: &OutputGeometry) -> bool {
+        self.x < other.x + other.width as i32 && other.x < self.x + self.width as i32
+    }
+}
+
+/// 输出在拼接画布中的矩形 (x, y, width, height)，单位为逻辑像素
+pub type SpanRect = (f64, f64, f64, f64);
+
+/// 计算所有输出拼接后的画布尺寸以及每个输出在画布中的位置
+/// 每个输出四周加上自身的边框，被边框遮住的部分不显示，画面在屏幕之间保持连续
+pub fn span_rects(outputs: &[OutputGeometry]) -> ((f64, f64), Vec<SpanRect>) {
+    let Some(min_x) = outputs.iter().map(|o| o.x).min() else {
+        return ((0.0, 0.0), Vec::new());
+    };
+    let min_y = outputs.iter().map(|o| o.y).min().unwrap_or(0);
+
+    let mut canvas = (0.0f64, 0.0f64);
+    let rects = outputs
+        .iter()
+        .map(|output| {
+            let bezel = output.bezel_px();
+            // 同一行中位于左侧的输出，以及同一列中位于上方的输出，两侧的边框都要让出来
+            let shift_x: f64 = outputs
+                .iter()
+                .filter(|o| o.x + o.width as i32 <= output.x && o.overlaps_rows(output))
+                .map(|o| 2.0 * o.bezel_px())
+                .sum();
+            let shift_y: f64 = outputs
+                .iter()
+                .filter(|o| o.y + o.height as i32 <= output.y && o.overlaps_columns(output))
+                .map(|o| 2.0 * o.bezel_px())
+                .sum();
+            let x = (output.x - min_x) as f64 + shift_x + bezel;
+            let y = (output.y - min_y) as f64 + shift_y + bezel;
+            let (width, height) = (output.width as f64, output.height as f64);
+            canvas.0 = canvas.0.max(x + width + bezel);
+            canvas.1 = canvas.1.max(y + height + bezel);
+            (x, y, width, height)
+        })
+        .collect();
+    (canvas, rects)
+}
+
+/// 视频帧按 fill 方式铺满整个画布时，`rect` 对应的帧内源矩形（帧像素）
+pub fn span_source(frame: (u32, u32), canvas: (f64, f64), rect: SpanRect) -> (f64, f64, f64, f64) {
+    let (fw, fh) = (frame.0 as f64, frame.1 as f64);
+    let (cw, ch) = canvas;
+    if cw <= 0.0 || ch <= 0.0 {
+        return (0.0, 0.0, fw, fh);
+    }
+    // 每个画布像素对应的帧像素数，裁剪掉宽高比不一致的部分
+    let k = (fw / cw).min(fh / ch);
+    let offset_x = (fw - cw * k) / 2.0;
+    let offset_y = (fh - ch * k) / 2.0;
+    let (x, y, w, h) = rect;
+    (offset_x + x * k, offset_y + y * k, w * k, h * k)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn output(x: i32, y: i32, bezel_mm: f64) -> OutputGeometry {
+        OutputGeometry { x, y, width: 1920, height: 1080, physical_width_mm: 480, bezel_mm }
+    }
+
+    #[test]
+    fn test_span_side_by_side() {
+        let (canvas, rects) = span_rects(&[output(1920, 0, 0.0), output(0, 0, 0.0)]);
+        assert_eq!(canvas, (3840.0, 1080.0));
+        assert_eq!(rects, vec![(1920.0, 0.0, 1920.0, 1080.0), (0.0, 0.0, 1920.0, 1080.0)]);
+
+        let source = span_source((3840, 1080), canvas, rects[0]);
+        assert_eq!(source, (1920.0, 0.0, 1920.0, 1080.0));
+    }
+
+    #[test]
+    fn test_span_bezel_gap() {
+        // 1920 像素 / 480 毫米 = 4 像素每毫米，5 毫米边框为 20 像素
+        let (canvas, rects) = span_rects(&[output(0, 0, 5.0), output(1920, 0, 5.0)]);
+        assert_eq!(rects[0].0, 20.0);
+        assert_eq!(rects[1].0, 1920.0 + 20.0 * 3.0);
+        assert_eq!(canvas, (3840.0 + 80.0, 1080.0 + 40.0));
+    }
+
+    #[test]
+    fn test_span_source_crops_to_canvas_aspect() {
+        // 16:9 视频铺满 32:9 画布，上下裁剪
+        let source = span_source((1920, 1080), (3840.0, 1080.0), (0.0, 0.0, 1920.0, 1080.0));
+        assert_eq!(source, (0.0, 270.0, 960.0, 540.0));
+    }
+}
@@ -1,88 +1,627 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{Seek, Write};
-use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::Interest;
+use tokio::io::unix::AsyncFd;
+use wayland_client::backend::WaylandError;
 use wayland_client::protocol::{
-    wl_buffer, wl_compositor, wl_display, wl_output, wl_registry, wl_seat, wl_shm, wl_shm_pool,
-    wl_surface,
+    wl_buffer, wl_callback, wl_compositor, wl_display, wl_output, wl_region, wl_registry, wl_seat,
+    wl_shm, wl_shm_pool, wl_surface,
 };
 use wayland_client::{
-    Connection, Dispatch, QueueHandle,
+    Connection, Dispatch, DispatchError, Proxy, QueueHandle,
     globals::{GlobalListContents, registry_queue_init},
 };
 use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
 use wayland_protocols::wp::viewporter::client::{wp_viewport, wp_viewporter};
-use wayland_protocols::wp::linux_dmabuf::zv1::client::{zwp_linux_dmabuf_v1, zwp_linux_buffer_params_v1};
+use wayland_protocols::wp::presentation_time::client::{wp_presentation, wp_presentation_feedback};
+use wayland_protocols::wp::linux_dmabuf::zv1::client::{
+    zwp_linux_buffer_params_v1, zwp_linux_dmabuf_feedback_v1, zwp_linux_dmabuf_v1,
+};
+use wayland_protocols::wp::fractional_scale::v1::client::{wp_fractional_scale_manager_v1, wp_fractional_scale_v1};
+use wayland_protocols::wp::alpha_modifier::v1::client::{wp_alpha_modifier_surface_v1, wp_alpha_modifier_v1};
+use wayland_protocols::xdg::xdg_output::zv1::client::{zxdg_output_manager_v1, zxdg_output_v1};
+
+use super::damage::{self, DamageRect};
+use super::format::{ColorQuality, PixelFormat};
+use super::layer::{Anchor, Layer, Margins};
+use super::layout::{self, Color, ScalingFilter, ScalingMode};
+use super::span::{self, OutputGeometry};
+
+/// 壁纸显示的目标输出
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OutputTarget {
+    /// 所有输出（已单独指定壁纸的输出除外）
+    All,
+    /// 指定名称的输出，例如 "DP-1"
+    Named(String),
+}
+
+impl OutputTarget {
+    pub fn from_name(name: Option<String>) -> Self {
+        match name {
+            Some(name) => OutputTarget::Named(name),
+            None => OutputTarget::All,
+        }
+    }
+}
+
+impl std::fmt::Display for OutputTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputTarget::All => write!(f, "all outputs"),
+            OutputTarget::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// 已被单独指定壁纸的输出名称集合
+/// `OutputTarget::All` 的壁纸不会在这些输出上创建 surface
+#[derive(Debug, Clone, Default)]
+pub struct ClaimedOutputs(Arc<Mutex<HashSet<String>>>);
+
+impl ClaimedOutputs {
+    pub fn claim(&self, name: &str) {
+        self.0.lock().unwrap().insert(name.to_string());
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.lock().unwrap().contains(name)
+    }
+}
+
+/// 与 compositor 的连接状态，通过 IPC 状态查询上报
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectionState {
+    /// 正在建立首次连接
+    Connecting,
+    /// 已连接
+    Connected,
+    /// 连接断开，正在重连（第 attempt 次尝试）
+    Reconnecting { attempt: u32 },
+}
+
+impl std::fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionState::Connecting => write!(f, "connecting"),
+            ConnectionState::Connected => write!(f, "connected"),
+            ConnectionState::Reconnecting { attempt } => write!(f, "reconnecting (attempt {})", attempt),
+        }
+    }
+}
+
+/// 默认的 layer surface namespace
+pub const DEFAULT_NAMESPACE: &str = "waypaper-rs";
+
+/// 壁纸 surface 的显示选项
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurfaceOptions {
+    pub scaling: ScalingMode,
+    /// fit/center/tile 模式下的背景色
+    pub background: Color,
+    /// 决定 SHM buffer 的像素格式
+    pub quality: ColorQuality,
+    /// 解码端缩放视频帧的算法
+    pub scaling_filter: ScalingFilter,
+    pub layer: Layer,
+    /// layer surface 的 namespace，compositor 规则（如 Hyprland layerrule）据此匹配
+    pub namespace: String,
+    pub anchor: Anchor,
+    pub margins: Margins,
+    /// 整个 surface 的不透明度（0.0 - 1.0），需要 wp_alpha_modifier_v1
+    pub opacity: f64,
+    /// span 模式下每个输出四周的边框宽度（毫米）
+    pub bezel: f64,
+    /// 按输出名称覆盖的边框宽度
+    pub output_bezels: HashMap<String, f64>,
+}
+
+impl Default for SurfaceOptions {
+    fn default() -> Self {
+        Self {
+            scaling: ScalingMode::default(),
+            background: Color::default(),
+            quality: ColorQuality::default(),
+            scaling_filter: ScalingFilter::default(),
+            layer: Layer::default(),
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            anchor: Anchor::default(),
+            margins: Margins::default(),
+            opacity: 1.0,
+            bezel: 0.0,
+            output_bezels: HashMap::new(),
+        }
+    }
+}
+
+impl SurfaceOptions {
+    fn is_translucent(&self) -> bool {
+        self.opacity < 1.0
+    }
+
+    fn bezel_for(&self, output: &OutputInfo) -> f64 {
+        output
+            .name
+            .as_ref()
+            .and_then(|name| self.output_bezels.get(name))
+            .copied()
+            .unwrap_or(self.bezel)
+    }
+}
+
+/// 隐式 modifier（由驱动决定布局）
+pub const DRM_FORMAT_MOD_INVALID: u64 = 0x00ff_ffff_ffff_ffff;
+
+/// compositor 支持的 DMA-BUF 格式（DRM fourcc）和 modifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DmabufFormat {
+    pub format: u32,
+    pub modifier: u64,
+}
+
+/// DMA-BUF 的一个平面
+#[derive(Debug, Clone, Copy)]
+pub struct DmabufPlane {
+    pub fd: RawFd,
+    pub offset: u32,
+    pub stride: u32,
+}
+
+/// 以 DMA-BUF 形式导出的一帧
+#[derive(Debug, Clone)]
+pub struct DmabufAttributes {
+    pub width: u32,
+    pub height: u32,
+    /// DRM fourcc，例如 NV12
+    pub format: u32,
+    pub modifier: u64,
+    pub planes: Vec<DmabufPlane>,
+}
+
+/// 需要由渲染任务处理的 Wayland 事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaylandEvent {
+    /// compositor 关闭了某个输出上的 layer surface（输出关闭、compositor 重载等）
+    SurfaceClosed { output_id: u32 },
+}
+
+/// wl_output 的状态
+pub struct OutputInfo {
+    /// wl_registry 中的全局名称，也用作 Dispatch 的 user data
+    pub global_name: u32,
+    pub output: wl_output::WlOutput,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub refresh: i32,
+    pub scale: i32,
+    pub transform: wl_output::Transform,
+    /// wl_output geometry 给出的全局位置
+    pub position: (i32, i32),
+    /// xdg_output 给出的逻辑位置和尺寸，比 wl_output 的更准确
+    pub logical_position: Option<(i32, i32)>,
+    pub logical_size: Option<(u32, u32)>,
+    /// 物理尺寸（毫米，未应用 transform）
+    pub physical_size_mm: (i32, i32),
+    pub xdg_output: Option<zxdg_output_v1::ZxdgOutputV1>,
+    /// 是否已收到 done 事件（属性完整）
+    pub done: bool,
+    /// surface 被 compositor 关闭，等到输出再次发送 done（重新启用、模式变化等）才重新创建
+    pub surface_closed: bool,
+}
+
+impl OutputInfo {
+    fn new(global_name: u32, output: wl_output::WlOutput) -> Self {
+        Self {
+            global_name,
+            output,
+            name: None,
+            description: None,
+            width: 1920, // Default to 1920x1080
+            height: 1080,
+            refresh: 0,
+            scale: 1,
+            transform: wl_output::Transform::Normal,
+            position: (0, 0),
+            logical_position: None,
+            logical_size: None,
+            physical_size_mm: (0, 0),
+            xdg_output: None,
+            done: false,
+            surface_closed: false,
+        }
+    }
+
+    /// 用于日志的输出名称
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("output-{}", self.global_name))
+    }
+
+    /// 应用 transform 后的模式尺寸（旋转 90/270 度时宽高互换）
+    pub fn transformed_size(&self) -> (u32, u32) {
+        match self.transform {
+            wl_output::Transform::_90
+            | wl_output::Transform::_270
+            | wl_output::Transform::Flipped90
+            | wl_output::Transform::Flipped270 => (self.height, self.width),
+            _ => (self.width, self.height),
+        }
+    }
+
+    /// 在全局坐标系中的逻辑几何信息，没有 xdg_output 时按模式尺寸和整数缩放推算
+    fn geometry(&self, bezel_mm: f64) -> OutputGeometry {
+        let (width, height) = self.logical_size.unwrap_or_else(|| {
+            let (width, height) = self.transformed_size();
+            let scale = self.scale.max(1) as u32;
+            (width / scale, height / scale)
+        });
+        let rotated = self.transformed_size() != (self.width, self.height);
+        let physical_width_mm = if rotated { self.physical_size_mm.1 } else { self.physical_size_mm.0 };
+        let (x, y) = self.logical_position.unwrap_or(self.position);
+        OutputGeometry {
+            x,
+            y,
+            width,
+            height,
+            physical_width_mm: physical_width_mm.max(0) as u32,
+            bezel_mm,
+        }
+    }
+}
+
+/// SHM 池中的一个缓冲区，每个缓冲区占用池中独立的一段区域
+pub struct ShmBuffer {
+    pub buffer: Option<wl_buffer::WlBuffer>,
+    /// 在 SHM 池中的字节偏移
+    pub offset: usize,
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    /// 已提交给 compositor 且尚未收到 release，此时不能写入
+    pub busy: bool,
+}
+
+impl ShmBuffer {
+    fn new(offset: usize) -> Self {
+        Self {
+            buffer: None,
+            offset,
+            width: 0,
+            height: 0,
+            format: PixelFormat::default(),
+            busy: false,
+        }
+    }
+}
+
+/// 单个输出上的 layer surface 及其 SHM 缓冲区
+pub struct OutputSurface {
+    /// 对应 OutputInfo::global_name
+    pub output_id: u32,
+    /// 同一输出上重建 surface 时递增，用于忽略旧 surface 迟到的 frame callback
+    pub generation: u64,
+    pub surface: wl_surface::WlSurface,
+    pub layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+    pub viewport: Option<wp_viewport::WpViewport>,
+    pub fractional_scale: Option<wp_fractional_scale_v1::WpFractionalScaleV1>,
+    /// 设置了 opacity 时用于调整整个 surface 的 alpha
+    pub alpha_modifier: Option<wp_alpha_modifier_surface_v1::WpAlphaModifierSurfaceV1>,
+    /// wp_fractional_scale_v1 建议的缩放（分母为 120），优先于 wl_output 的整数缩放
+    pub preferred_scale: Option<u32>,
+    // 使用三缓冲，减少同步等待
+    pub buffers: Vec<ShmBuffer>,
+    pub current_buffer_index: usize,
+    pub shm_pool: wl_shm_pool::WlShmPool,
+    pub shm_file: File,
+    pub shm_data: *mut u8, // mmap 映射的内存
+    pub pool_size: i32,
+    /// 每个缓冲区在池中占用的字节数，帧变大时随池一起扩容
+    pub slot_size: usize,
+    /// 扩容前创建、compositor 尚未 release 的 buffer，release 后销毁
+    pub retired_buffers: Vec<wl_buffer::WlBuffer>,
+    pub configured: bool,
+    pub configured_width: u32,
+    pub configured_height: u32,
+    /// 已请求 frame callback 但 compositor 尚未回调，此时不提交新帧
+    pub frame_pending: bool,
+    /// 上一次提交的 SHM buffer，用于计算新帧的损坏区域
+    pub last_shm_buffer: Option<usize>,
+    /// 上一次提交时的布局和逻辑尺寸，变化时整个 buffer 都需要重绘
+    pub last_geometry: Option<(layout::Layout, (u32, u32))>,
+    /// 已设置为不透明区域的逻辑尺寸
+    pub opaque_size: Option<(u32, u32)>,
+    /// wp_presentation 反馈的实际刷新间隔
+    pub refresh_interval: Option<Duration>,
+    pub presented_frames: u64,
+    pub discarded_frames: u64,
+}
+
+impl OutputSurface {
+    /// 销毁 surface 及其缓冲区（mmap 在 Drop 中解除）
+    fn destroy(self) {
+        for buffer in self.buffers.iter().filter_map(|b| b.buffer.as_ref()).chain(&self.retired_buffers) {
+            buffer.destroy();
+        }
+        if let Some(ref viewport) = self.viewport {
+            viewport.destroy();
+        }
+        if let Some(ref fractional_scale) = self.fractional_scale {
+            fractional_scale.destroy();
+        }
+        if let Some(ref alpha_modifier) = self.alpha_modifier {
+            alpha_modifier.destroy();
+        }
+        self.layer_surface.destroy();
+        self.surface.destroy();
+        self.shm_pool.destroy();
+    }
+}
+
+impl OutputSurface {
+    /// surface 的缩放系数：优先使用 fractional scale，否则使用输出的整数缩放
+    fn scale(&self, outputs: &[OutputInfo]) -> f64 {
+        match self.preferred_scale {
+            Some(scale) => scale as f64 / 120.0,
+            None => outputs
+                .iter()
+                .find(|o| o.global_name == self.output_id)
+                .map(|o| o.scale.max(1) as f64)
+                .unwrap_or(1.0),
+        }
+    }
+
+    /// surface 的逻辑尺寸（viewport 目标矩形使用逻辑坐标）
+    /// 以 layer surface configure 的尺寸为准，compositor 未给出尺寸时按输出模式推算
+    fn logical_size(&self, outputs: &[OutputInfo]) -> (u32, u32) {
+        if self.configured_width > 0 && self.configured_height > 0 {
+            return (self.configured_width, self.configured_height);
+        }
+        let (width, height) = outputs
+            .iter()
+            .find(|o| o.global_name == self.output_id)
+            .map(|o| o.transformed_size())
+            .unwrap_or((1920, 1080));
+        let scale = self.scale(outputs);
+        ((width as f64 / scale).round() as u32, (height as f64 / scale).round() as u32)
+    }
+
+    /// surface 对应的物理像素尺寸
+    fn physical_size(&self, outputs: &[OutputInfo]) -> (u32, u32) {
+        let (width, height) = self.logical_size(outputs);
+        let scale = self.scale(outputs);
+        ((width as f64 * scale).round() as u32, (height as f64 * scale).round() as u32)
+    }
+
+    /// 确保每个缓冲区至少能容纳 `frame_size` 字节，不够时通过 wl_shm_pool.resize 扩容并重新 mmap
+    /// compositor 可能还在读取旧的 buffer，这时新的缓冲区放在旧区域之后，旧区域在全部 release 后回收
+    fn ensure_slot_size(&mut self, frame_size: usize) -> Result<()> {
+        if frame_size <= self.slot_size {
+            return Ok(());
+        }
+
+        let in_use = !self.retired_buffers.is_empty() || self.buffers.iter().any(|b| b.busy);
+        let base = if in_use { self.pool_size as usize } else { 0 };
+        let new_pool_size = frame_size
+            .checked_mul(BUFFER_COUNT)
+            .and_then(|size| size.checked_add(base))
+            .filter(|&size| size <= i32::MAX as usize)
+            .ok_or_else(|| anyhow::anyhow!("Frame size {} is too large for a SHM pool", frame_size))?;
+
+        // wl_shm_pool 只能变大，先扩展文件再通知 compositor
+        if new_pool_size > self.pool_size as usize {
+            self.shm_file.set_len(new_pool_size as u64)?;
+            let new_data = map_shm(&self.shm_file, new_pool_size)?;
+            unsafe {
+                libc::munmap(self.shm_data as *mut libc::c_void, self.pool_size as usize);
+            }
+            self.shm_data = new_data;
+            self.shm_pool.resize(new_pool_size as i32);
+
+            log::info!(
+                "Resized SHM pool on output {}: {} -> {} bytes",
+                self.output_id, self.pool_size, new_pool_size
+            );
+            self.pool_size = new_pool_size as i32;
+        }
+        self.slot_size = frame_size;
+
+        // 缓冲区偏移改变，重建 wl_buffer；compositor 还在读取的留到 release 时销毁
+        for (i, shm_buffer) in self.buffers.iter_mut().enumerate() {
+            if let Some(buffer) = shm_buffer.buffer.take() {
+                if shm_buffer.busy {
+                    self.retired_buffers.push(buffer);
+                } else {
+                    buffer.destroy();
+                }
+            }
+            *shm_buffer = ShmBuffer::new(base + i * frame_size);
+        }
+        self.current_buffer_index = 0;
+        self.last_shm_buffer = None;
+
+        Ok(())
+    }
+
+    /// 旧 buffer 被 release 后销毁；全部销毁后释放旧区域占用的内存，下次扩容时从池的开头重新分配
+    fn release_retired(&mut self, buffer: &wl_buffer::WlBuffer) -> bool {
+        let Some(index) = self.retired_buffers.iter().position(|b| b == buffer) else {
+            return false;
+        };
+        self.retired_buffers.swap_remove(index).destroy();
+        let start = self.buffers.first().map_or(0, |b| b.offset);
+        if self.retired_buffers.is_empty() && start > 0 {
+            let ret = unsafe {
+                libc::fallocate(
+                    self.shm_file.as_raw_fd(),
+                    libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                    0,
+                    start as libc::off_t,
+                )
+            };
+            if ret != 0 {
+                log::debug!("Failed to free old SHM region: {}", std::io::Error::last_os_error());
+            }
+        }
+        true
+    }
+
+    /// 格式不透明时把整个 surface 声明为不透明区域，compositor 就不用再混合下面的内容
+    fn update_opaque_region(
+        &mut self,
+        compositor: Option<&wl_compositor::WlCompositor>,
+        outputs: &[OutputInfo],
+        qh: &QueueHandle<WaylandApp>,
+        opaque: bool,
+    ) {
+        let size = opaque.then(|| self.logical_size(outputs));
+        if size == self.opaque_size {
+            return;
+        }
+        match (size, compositor) {
+            (Some((width, height)), Some(compositor)) => {
+                let region = compositor.create_region(qh, ());
+                region.add(0, 0, width as i32, height as i32);
+                self.surface.set_opaque_region(Some(&region));
+                region.destroy();
+            }
+            _ => self.surface.set_opaque_region(None),
+        }
+        self.opaque_size = size;
+    }
+}
+
+/// 将 SHM 文件映射到内存
+fn map_shm(file: &File, size: usize) -> Result<*mut u8> {
+    // 使用 mmap 映射 SHM 文件，避免每次写入时的系统调用
+    let data = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+
+    if data == libc::MAP_FAILED {
+        return Err(anyhow::anyhow!("Failed to mmap SHM file"));
+    }
+
+    Ok(data as *mut u8)
+}
+
+impl Drop for OutputSurface {
+    fn drop(&mut self) {
+        // 连接断开后 WaylandApp 会被直接丢弃，这里保证 mmap 总能被释放
+        unsafe {
+            libc::munmap(self.shm_data as *mut libc::c_void, self.pool_size as usize);
+        }
+    }
+}
 
 pub struct WaylandApp {
+    /// 用于在 tokio 中等待 Wayland socket 可读（放在最前面，先于连接释放）
+    poll_fd: Option<AsyncFd<RawFd>>,
     pub conn: Connection,
     pub display: wl_display::WlDisplay,
     pub compositor: Option<wl_compositor::WlCompositor>,
     pub layer_shell: Option<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
     pub shm: Option<wl_shm::WlShm>,
-    pub surface: Option<wl_surface::WlSurface>,
-    pub layer_surface: Option<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
-    pub buffer: Option<wl_buffer::WlBuffer>,
-    // 使用三缓冲，减少同步等待
-    pub buffers: Vec<Option<wl_buffer::WlBuffer>>,
-    pub current_buffer_index: usize,
-    pub shm_pool: Option<wl_shm_pool::WlShmPool>,
-    pub shm_file: Option<File>,
-    pub shm_data: Option<*mut u8>, // mmap 映射的内存
+    /// compositor 通过 wl_shm.format 公布的像素格式
+    pub shm_formats: Vec<wl_shm::Format>,
     pub queue: Option<wayland_client::EventQueue<WaylandApp>>,
-    pub configured: bool,
-    pub configured_width: u32,
-    pub configured_height: u32,
     pub frame_count: u64,
-    pub pool_size: i32,
-    pub output_width: u32,
-    pub output_height: u32,
+    /// 连接已断开（compositor 崩溃或重启），需要重建 WaylandApp
+    pub connection_lost: bool,
+    // 多输出支持
+    pub target: OutputTarget,
+    pub claimed: ClaimedOutputs,
+    pub options: SurfaceOptions,
+    pub outputs: Vec<OutputInfo>,
+    pub surfaces: Vec<OutputSurface>,
+    pub events: Vec<WaylandEvent>,
+    next_surface_generation: u64,
     // Viewporter 支持
     pub viewporter: Option<wp_viewporter::WpViewporter>,
-    pub viewport: Option<wp_viewport::WpViewport>,
+    // Presentation time 支持（可选，用于获取真实刷新间隔）
+    pub presentation: Option<wp_presentation::WpPresentation>,
+    // Fractional scale 支持（可选）
+    pub fractional_scale_manager: Option<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1>,
+    pub alpha_modifier: Option<wp_alpha_modifier_v1::WpAlphaModifierV1>,
+    pub xdg_output_manager: Option<zxdg_output_manager_v1::ZxdgOutputManagerV1>,
     // DMA-BUF 支持
     pub linux_dmabuf: Option<zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1>,
-    pub dmabuf_formats: Vec<u32>,
+    /// compositor 可以导入的格式/modifier（v4 feedback 或 v3 modifier 事件）
+    pub dmabuf_formats: Vec<DmabufFormat>,
+    /// compositor 使用的 DRM 设备（dev_t），来自 v4 feedback 的 main_device
+    pub dmabuf_main_device: Option<u64>,
+    dmabuf_feedback: Option<zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1>,
+    /// feedback 的格式表，tranche 中的索引指向这里
+    dmabuf_format_table: Vec<DmabufFormat>,
+    /// 正在接收的 feedback，收到 done 后替换 dmabuf_formats
+    dmabuf_pending_formats: Vec<DmabufFormat>,
+    /// compositor 拒绝导入 DMA-BUF，之后只使用 SHM
+    dmabuf_failed: bool,
+    /// 已提交的 DMA-BUF buffer，以及需要保持到 release 的帧（释放后解码器才能复用该 surface）
+    dmabuf_buffers: Vec<(u64, wl_buffer::WlBuffer, Box<dyn std::any::Any + Send>)>,
+    next_dmabuf_id: u64,
 }
 
 // 实现 Send 以便在异步任务中使用
 unsafe impl Send for WaylandApp {}
 
+const BUFFER_COUNT: usize = 3;
+
 impl WaylandApp {
-    pub fn new() -> Result<Self> {
+    pub fn new(target: OutputTarget, claimed: ClaimedOutputs, options: SurfaceOptions) -> Result<Self> {
         let conn = Connection::connect_to_env()?;
         let conn_clone = conn.clone();
         let display = conn_clone.display();
 
-        // Calculate pool size for 4K support (3840x2160 * 4 bytes/pixel)
-        let pool_size = 3840 * 2160 * 4;
-
         let mut app = Self {
+            poll_fd: None,
             conn,
             display: display.clone(),
             compositor: None,
             layer_shell: None,
             shm: None,
-            surface: None,
-            layer_surface: None,
-            buffer: None,
-            buffers: vec![None, None, None], // 三缓冲
-            current_buffer_index: 0,
-            shm_pool: None,
-            shm_file: None,
-            shm_data: None,
+            shm_formats: Vec::new(),
             queue: None,
-            configured: false,
-            configured_width: 0,
-            configured_height: 0,
             frame_count: 0,
-            pool_size,
-            output_width: 1920, // Default to 1920x1080
-            output_height: 1080,
+            connection_lost: false,
+            target,
+            claimed,
+            options,
+            outputs: Vec::new(),
+            surfaces: Vec::new(),
+            events: Vec::new(),
             viewporter: None,
-            viewport: None,
+            presentation: None,
+            fractional_scale_manager: None,
+            alpha_modifier: None,
+            xdg_output_manager: None,
             linux_dmabuf: None,
             dmabuf_formats: Vec::new(),
+            dmabuf_main_device: None,
+            dmabuf_feedback: None,
+            dmabuf_format_table: Vec::new(),
+            dmabuf_pending_formats: Vec::new(),
+            dmabuf_failed: false,
+            dmabuf_buffers: Vec::new(),
+            next_dmabuf_id: 0,
+            next_surface_generation: 0,
         };
         
         // Create event queue
@@ -106,230 +645,724 @@ impl WaylandApp {
             return Err(anyhow::anyhow!("Failed to bind Wayland globals"));
         }
 
-        // Create reusable SHM pool with mmap
-        let shm = app.shm.as_ref().unwrap();
-        let mut shm_file = tempfile::tempfile()?;
-        shm_file.set_len(app.pool_size as u64)?;
-
-        // 使用 mmap 映射 SHM 文件，避免每次写入时的系统调用
-        let shm_data = unsafe {
-            libc::mmap(
-                std::ptr::null_mut(),
-                app.pool_size as usize,
-                libc::PROT_READ | libc::PROT_WRITE,
-                libc::MAP_SHARED,
-                shm_file.as_raw_fd(),
-                0,
-            )
-        };
+        // linux-dmabuf v4 通过 feedback 对象报告格式表和 modifier
+        if let Some(ref linux_dmabuf) = app.linux_dmabuf {
+            if linux_dmabuf.version() >= 4 {
+                app.dmabuf_feedback = Some(linux_dmabuf.get_default_feedback(&qh, ()));
+                queue.roundtrip(&mut app)?;
+            }
+            log::info!("DMA-BUF: {} format/modifier pairs supported", app.dmabuf_formats.len());
+        }
 
-        if shm_data == libc::MAP_FAILED {
-            return Err(anyhow::anyhow!("Failed to mmap SHM file"));
+        // Wait for output properties (name, mode, scale ...)
+        iterations = 0;
+        while app.outputs.iter().any(|o| !o.done) && iterations < 20 {
+            queue.roundtrip(&mut app)?;
+            iterations += 1;
         }
 
-        let shm_pool = shm.create_pool(shm_file.as_fd(), app.pool_size, &qh, ());
+        // 为每个匹配的输出创建 layer surface
+        let output_ids: Vec<u32> = app
+            .outputs
+            .iter()
+            .filter(|o| app.wants_output(o))
+            .map(|o| o.global_name)
+            .collect();
+        for output_id in output_ids {
+            app.create_surface(output_id, &qh)?;
+        }
 
-        app.shm_file = Some(shm_file);
-        app.shm_pool = Some(shm_pool);
-        app.shm_data = Some(shm_data as *mut u8);
+        if app.surfaces.is_empty() {
+            log::warn!("No output matches {}", app.target);
+        }
         
-        // Create surface and layer surface
-        let compositor = app.compositor.as_ref().unwrap();
-        let layer_shell = app.layer_shell.as_ref().unwrap();
+        // Wait for configure
+        iterations = 0;
+        while app.surfaces.iter().any(|s| !s.configured) && iterations < 20 {
+            queue.roundtrip(&mut app)?;
+            iterations += 1;
+        }
         
-        let surface = compositor.create_surface(&qh, ());
-        app.surface = Some(surface.clone());
+        app.queue = Some(queue);
+        Ok(app)
+    }
+
+    /// 判断该输出是否应显示本壁纸
+    fn wants_output(&self, output: &OutputInfo) -> bool {
+        match &self.target {
+            OutputTarget::All => match output.name {
+                Some(ref name) => !self.claimed.contains(name),
+                None => true,
+            },
+            OutputTarget::Named(target) => output.name.as_deref() == Some(target.as_str()),
+        }
+    }
+
+    /// 在指定输出上创建 layer surface 和 SHM 池
+    fn create_surface(&mut self, output_id: u32, qh: &QueueHandle<Self>) -> Result<()> {
+        let output = self
+            .outputs
+            .iter()
+            .find(|o| o.global_name == output_id)
+            .ok_or_else(|| anyhow::anyhow!("Output {} not found", output_id))?;
+        let compositor = self.compositor.as_ref().ok_or_else(|| anyhow::anyhow!("Compositor not available"))?;
+        let layer_shell = self.layer_shell.as_ref().ok_or_else(|| anyhow::anyhow!("Layer shell not available"))?;
+        let shm = self.shm.as_ref().ok_or_else(|| anyhow::anyhow!("SHM not available"))?;
+
+        // Create reusable SHM pool with mmap
+        // 初始按输出分辨率分配，帧更大时在 render_frame 中扩容
+        let slot_size = output.width as usize * output.height as usize * 4;
+        let pool_size = slot_size * BUFFER_COUNT;
+        let shm_file = tempfile::tempfile()?;
+        shm_file.set_len(pool_size as u64)?;
+        let shm_data = map_shm(&shm_file, pool_size)?;
+
+        let shm_pool = shm.create_pool(shm_file.as_fd(), pool_size as i32, qh, ());
+
+        // Create surface and layer surface
+        let surface = compositor.create_surface(qh, ());
         
+        let options = &self.options;
         let layer_surface = layer_shell.get_layer_surface(
             &surface,
-            None,
-            zwlr_layer_shell_v1::Layer::Background,
-            "waypaper-rs".to_string(),
-            &qh,
-            (),
+            Some(&output.output),
+            options.layer.wl_layer(),
+            options.namespace.clone(),
+            qh,
+            output_id,
         );
-        app.layer_surface = Some(layer_surface.clone());
         
         // Configure layer surface
-        layer_surface.set_size(0, 0);
-        layer_surface.set_anchor(
-            zwlr_layer_surface_v1::Anchor::Top 
-            | zwlr_layer_surface_v1::Anchor::Bottom 
-            | zwlr_layer_surface_v1::Anchor::Left 
-            | zwlr_layer_surface_v1::Anchor::Right
-        );
+        // 两边都锚定的方向由 compositor 决定尺寸，否则按输出的逻辑尺寸减去边距
+        let (stretch_x, stretch_y) = options.anchor.is_stretched();
+        let (output_width, output_height) = output.transformed_size();
+        let output_scale = output.scale.max(1);
+        let margins = options.margins;
+        let width = if stretch_x { 0 } else { (output_width as i32 / output_scale - margins.left - margins.right).max(1) };
+        let height = if stretch_y { 0 } else { (output_height as i32 / output_scale - margins.top - margins.bottom).max(1) };
+        layer_surface.set_size(width as u32, height as u32);
+        layer_surface.set_anchor(options.anchor.wl_anchor());
+        layer_surface.set_margin(margins.top, margins.right, margins.bottom, margins.left);
         layer_surface.set_exclusive_zone(-1);
         layer_surface.set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::None);
+        // 空的输入区域：overlay/top 层上的壁纸不拦截鼠标事件
+        let input_region = compositor.create_region(qh, ());
+        surface.set_input_region(Some(&input_region));
+        input_region.destroy();
         
         surface.commit();
         
         // 创建 viewport（如果支持）
-        if let Some(ref viewporter) = app.viewporter {
-            app.viewport = Some(viewporter.get_viewport(&surface, &qh, ()));
-            log::info!("Created viewport for surface");
+        let viewport = self.viewporter.as_ref().map(|viewporter| viewporter.get_viewport(&surface, qh, ()));
+        // fractional scale 需要配合 viewport 使用
+        let fractional_scale = match (&self.fractional_scale_manager, &viewport) {
+            (Some(manager), Some(_)) => Some(manager.get_fractional_scale(&surface, qh, output_id)),
+            _ => None,
+        };
+        let alpha_modifier = if options.is_translucent() {
+            match self.alpha_modifier {
+                Some(ref manager) => {
+                    let alpha_modifier = manager.get_surface(&surface, qh, ());
+                    let multiplier = (options.opacity.clamp(0.0, 1.0) * u32::MAX as f64).round() as u32;
+                    alpha_modifier.set_multiplier(multiplier);
+                    Some(alpha_modifier)
+                }
+                None => {
+                    log::warn!("Compositor does not support wp_alpha_modifier_v1, ignoring opacity {}", options.opacity);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        log::info!(
+            "Created layer surface on {} (layer: {}, namespace: {})",
+            output.display_name(), options.layer, options.namespace
+        );
+
+        let generation = self.next_surface_generation;
+        self.next_surface_generation += 1;
+        self.surfaces.push(OutputSurface {
+            output_id,
+            generation,
+            surface,
+            layer_surface,
+            viewport,
+            fractional_scale,
+            alpha_modifier,
+            preferred_scale: None,
+            buffers: (0..BUFFER_COUNT).map(|i| ShmBuffer::new(i * slot_size)).collect(), // 三缓冲
+            current_buffer_index: 0,
+            shm_pool,
+            shm_file,
+            shm_data,
+            pool_size: pool_size as i32,
+            slot_size,
+            retired_buffers: Vec::new(),
+            configured: false,
+            configured_width: 0,
+            configured_height: 0,
+            frame_pending: false,
+            last_shm_buffer: None,
+            last_geometry: None,
+            opaque_size: None,
+            refresh_interval: None,
+            presented_frames: 0,
+            discarded_frames: 0,
+        });
+
+        Ok(())
+    }
+
+    /// 根据当前输出列表同步 surface：
+    /// 为新出现（热插拔）的匹配输出创建 surface，销毁不再需要的 surface（例如输出已被单独指定了壁纸）
+    fn sync_surfaces(&mut self, qh: &QueueHandle<Self>) {
+        let mut i = 0;
+        while i < self.surfaces.len() {
+            let output_id = self.surfaces[i].output_id;
+            let wanted = self
+                .outputs
+                .iter()
+                .find(|o| o.global_name == output_id)
+                .is_some_and(|o| self.wants_output(o));
+            if wanted {
+                i += 1;
+            } else {
+                log::info!("Removing layer surface from output {}", output_id);
+                self.surfaces.swap_remove(i).destroy();
+            }
         }
-        
-        // Wait for configure
-        iterations = 0;
-        while !app.configured && iterations < 20 {
-            queue.roundtrip(&mut app)?;
-            iterations += 1;
+
+        let new_outputs: Vec<u32> = self
+            .outputs
+            .iter()
+            .filter(|o| o.done && !o.surface_closed && self.wants_output(o))
+            .filter(|o| !self.surfaces.iter().any(|s| s.output_id == o.global_name))
+            .map(|o| o.global_name)
+            .collect();
+        for output_id in new_outputs {
+            if let Err(e) = self.create_surface(output_id, qh) {
+                log::error!("Failed to create surface for output {}: {}", output_id, e);
+            }
         }
-        
-        app.queue = Some(queue);
-        Ok(app)
     }
 
-    pub fn render_frame(&mut self, frame_data: &[u8], width: u32, height: u32) -> Result<()> {
-        if !self.configured {
-            return Ok(());
+    /// 输出被移除（拔出显示器等）时，销毁对应的 surface、缓冲区并释放 wl_output
+    fn remove_output(&mut self, global_name: u32) {
+        let Some(index) = self.outputs.iter().position(|o| o.global_name == global_name) else {
+            return;
+        };
+        let output = self.outputs.remove(index);
+        log::info!("Output {} removed", output.display_name());
+
+        if let Some(index) = self.surfaces.iter().position(|s| s.output_id == global_name) {
+            self.surfaces.swap_remove(index).destroy();
+        }
+        if let Some(ref xdg_output) = output.xdg_output {
+            xdg_output.destroy();
         }
+        if output.output.version() >= 3 {
+            output.output.release();
+        }
+    }
 
-        let surface = self
-            .surface
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Surface not available"))?;
-        let shm_pool = self.shm_pool.as_ref().ok_or_else(|| anyhow::anyhow!("SHM pool not available"))?;
+    /// 取出待处理的事件
+    pub fn take_events(&mut self) -> Vec<WaylandEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// 销毁被 compositor 关闭的 surface
+    /// 输出再次发送 done 事件（重新启用、模式变化）或者重新接入（新的 wl_output global）时重新创建，不轮询
+    pub fn recreate_surface(&mut self, output_id: u32) {
+        if let Some(index) = self.surfaces.iter().position(|s| s.output_id == output_id) {
+            self.surfaces.swap_remove(index).destroy();
+        }
+        if let Some(output) = self.outputs.iter_mut().find(|o| o.global_name == output_id) {
+            output.surface_closed = true;
+            log::info!("Layer surface on {} will be recreated when the output is reconfigured", output.display_name());
+        } else {
+            log::info!("Output {} is gone, waiting for it to come back", output_id);
+        }
+    }
+
+    /// 与 compositor 的连接是否已断开
+    pub fn is_connection_lost(&self) -> bool {
+        self.connection_lost
+    }
 
+    /// 是否至少有一个输出在显示本壁纸
+    pub fn has_surfaces(&self) -> bool {
+        !self.surfaces.is_empty()
+    }
+
+    /// 所有 surface 中最大的物理像素尺寸，用作解码器的缩放目标
+    /// span 模式下为整个拼接画布的物理像素尺寸
+    pub fn target_size(&self) -> Option<(u32, u32)> {
+        if self.options.scaling == ScalingMode::Span {
+            let ((width, height), _) = self.span_rects()?;
+            let scale = self
+                .surfaces
+                .iter()
+                .map(|s| s.scale(&self.outputs))
+                .fold(1.0, f64::max);
+            return Some(((width * scale).round() as u32, (height * scale).round() as u32));
+        }
+        self.surfaces
+            .iter()
+            .filter(|s| s.configured)
+            .map(|s| s.physical_size(&self.outputs))
+            .reduce(|(w1, h1), (w2, h2)| (w1.max(w2), h1.max(h2)))
+    }
+
+    /// span 模式下所有 surface 拼接后的画布和每个 surface 的位置（与 surfaces 顺序一致）
+    fn span_rects(&self) -> Option<((f64, f64), Vec<span::SpanRect>)> {
+        let geometries: Vec<OutputGeometry> = self
+            .surfaces
+            .iter()
+            .map(|s| {
+                let output = self.outputs.iter().find(|o| o.global_name == s.output_id)?;
+                Some(output.geometry(self.options.bezel_for(output)))
+            })
+            .collect::<Option<_>>()?;
+        if geometries.is_empty() {
+            return None;
+        }
+        Some(span::span_rects(&geometries))
+    }
+
+    /// 计算帧在每个 surface 上的布局（与 surfaces 顺序一致）
+    fn compute_layouts(&self, frame: (u32, u32)) -> Vec<layout::Layout> {
+        if self.options.scaling == ScalingMode::Span {
+            if let Some((canvas, rects)) = self.span_rects() {
+                return rects
+                    .into_iter()
+                    .map(|rect| layout::Layout {
+                        canvas: frame,
+                        placements: vec![(0, 0)],
+                        source: span::span_source(frame, canvas, rect),
+                    })
+                    .collect();
+            }
+        }
+        self.surfaces
+            .iter()
+            .map(|s| layout::compute_layout(self.options.scaling, frame, s.physical_size(&self.outputs)))
+            .collect()
+    }
+
+    /// 按画质设置和 compositor 支持的格式选择 SHM 像素格式
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.options
+            .quality
+            .preferred_formats()
+            .iter()
+            .copied()
+            // Argb8888 和 Xrgb8888 是 wl_shm 必须支持的格式
+            .find(|f| matches!(f, PixelFormat::Argb8888 | PixelFormat::Xrgb8888) || self.shm_formats.contains(&f.wl_format()))
+            .unwrap_or(PixelFormat::Argb8888)
+    }
+
+    pub fn render_frame(&mut self, frame_data: &[u8], width: u32, height: u32, format: PixelFormat) -> Result<()> {
         let queue = self.queue.as_mut().ok_or_else(|| anyhow::anyhow!("Queue not available"))?;
         let qh = queue.handle();
 
-        let stride = width * 4;
-        let size = stride * height;
+        let bpp = format.bytes_per_pixel();
+        let size = width as usize * height as usize * bpp as usize;
+        if frame_data.len() < size {
+            return Err(anyhow::anyhow!("Frame data too short: {} < {}", frame_data.len(), size));
+        }
+        let layouts = self.compute_layouts((width, height));
+        let background = format.encode(self.options.background);
+        let opaque = format.is_opaque() && !self.options.is_translucent();
+
+        // Debug: log first few pixels every 30 frames
+        self.frame_count += 1;
+        if self.frame_count % 30 == 0 {
+            log::info!("Frame {} - {}x{} {:?} - First 8 bytes: {}, {}, {}, {}, {}, {}, {}, {}",
+                     self.frame_count, width, height, format,
+                     frame_data[0], frame_data[1], frame_data[2], frame_data[3],
+                     frame_data[4], frame_data[5], frame_data[6], frame_data[7]);
+        }
+
+        let mut write_time = std::time::Duration::ZERO;
+        let mut buffer_time = std::time::Duration::ZERO;
+        let mut commit_time = std::time::Duration::ZERO;
+
+        // 只向 frame callback 已返回的 surface 提交新帧，被遮挡的输出不会消耗资源
+        // 布局按缩放模式计算 buffer 尺寸和帧的位置
+        for (output_surface, layout) in self.surfaces.iter_mut().zip(layouts).filter(|(s, _)| s.configured && !s.frame_pending) {
+            let (buffer_width, buffer_height) = layout.canvas;
+            let stride = buffer_width * bpp;
+            let buffer_size = stride as usize * buffer_height as usize;
+
+            // 帧比当前缓冲区大时扩容 SHM 池
+            output_surface.ensure_slot_size(buffer_size)?;
+
+            // 从当前位置开始找一个 compositor 已经 release 的 buffer
+            let buffer_start = std::time::Instant::now();
+            let buffer_count = output_surface.buffers.len();
+            let Some(index) = (0..buffer_count)
+                .map(|i| (output_surface.current_buffer_index + i) % buffer_count)
+                .find(|&i| !output_surface.buffers[i].busy)
+            else {
+                // 所有 buffer 都还在被 compositor 读取，丢弃这一帧
+                log::debug!("No released buffer on output {}, dropping frame", output_surface.output_id);
+                continue;
+            };
+            let output_id = output_surface.output_id;
+
+            // 与上一次提交的 buffer 比较，布局和尺寸都没变时只提交变化的区域
+            let logical_size = output_surface.logical_size(&self.outputs);
+            let geometry = (layout.clone(), logical_size);
+            let previous_offset = output_surface
+                .last_shm_buffer
+                .filter(|&i| i != index && output_surface.last_geometry.as_ref() == Some(&geometry))
+                .map(|i| &output_surface.buffers[i])
+                .filter(|b| b.buffer.is_some() && b.format == format)
+                .map(|b| b.offset);
+
+            let shm_buffer = &mut output_surface.buffers[index];
+
+            // 尺寸或格式变化时重建 buffer
+            if shm_buffer.width != buffer_width || shm_buffer.height != buffer_height || shm_buffer.format != format {
+                if let Some(old) = shm_buffer.buffer.take() {
+                    old.destroy();
+                }
+            }
+            let buffer = if let Some(ref buf) = shm_buffer.buffer {
+                buf.clone()
+            } else {
+                // 创建新的 buffer，位于池中该槽位自己的区域
+                let new_buffer = output_surface.shm_pool.create_buffer(
+                    shm_buffer.offset as i32,
+                    buffer_width as i32,
+                    buffer_height as i32,
+                    stride as i32,
+                    format.wl_format(),
+                    &qh,
+                    (output_id, index),
+                );
+                shm_buffer.buffer = Some(new_buffer.clone());
+                shm_buffer.width = buffer_width;
+                shm_buffer.height = buffer_height;
+                shm_buffer.format = format;
+                new_buffer
+            };
+            buffer_time += buffer_start.elapsed();
+
+            // 使用 mmap 直接写入该 buffer 的区域，避免系统调用
+            let write_start = std::time::Instant::now();
+            unsafe {
+                let dst_ptr = output_surface.shm_data.add(shm_buffer.offset);
+                if layout.is_direct((width, height)) {
+                    // 使用 memcpy 直接拷贝到 mmap 区域
+                    std::ptr::copy_nonoverlapping(frame_data.as_ptr(), dst_ptr, size);
+                } else {
+                    let dst = std::slice::from_raw_parts_mut(dst_ptr, buffer_size);
+                    layout::compose(dst, &layout, frame_data, (width, height), &background);
+                }
+            }
+            let damage = match previous_offset {
+                Some(previous_offset) => unsafe {
+                    let old = std::slice::from_raw_parts(output_surface.shm_data.add(previous_offset), buffer_size);
+                    let new = std::slice::from_raw_parts(output_surface.shm_data.add(shm_buffer.offset), buffer_size);
+                    damage::diff_rects(old, new, buffer_width, buffer_height, stride, bpp)
+                },
+                None => vec![DamageRect::full(buffer_width, buffer_height)],
+            };
+            write_time += write_start.elapsed();
+
+            if damage.is_empty() {
+                // 画面没有变化，不提交，buffer 留到下一帧复用
+                log::trace!("Frame unchanged on output {}, skipping commit", output_id);
+                continue;
+            }
+
+            // 提交后直到收到 release 之前都不能再写入
+            shm_buffer.busy = true;
+
+            // 切换到下一个 buffer
+            output_surface.current_buffer_index = (index + 1) % buffer_count;
+
+            // Attach and commit
+            let commit_start = std::time::Instant::now();
+            output_surface.update_opaque_region(self.compositor.as_ref(), &self.outputs, &qh, opaque);
+            Self::present(output_surface, &self.outputs, self.presentation.as_ref(), &qh, &buffer, &layout, &damage);
+            output_surface.last_shm_buffer = Some(index);
+            commit_time += commit_start.elapsed();
+        }
+
+        // 立即发送请求，渲染任务之后可能阻塞在帧通道上
+        self.flush()?;
 
-        // Check if pool size is sufficient
-        if size as i32 > self.pool_size {
-            return Err(anyhow::anyhow!("Frame size {} exceeds pool size {}", size, self.pool_size));
+        // Log timing every 30 frames
+        static COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+        let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        if count % 30 == 0 {
+            log::info!("Render timing ({} surfaces): mmap_write={:.2}ms, buffer_get={:.2}ms, commit={:.2}ms",
+                     self.surfaces.len(),
+                     write_time.as_secs_f64() * 1000.0,
+                     buffer_time.as_secs_f64() * 1000.0,
+                     commit_time.as_secs_f64() * 1000.0);
+        }
+
+        Ok(())
+    }
+
+    /// 把 buffer 提交到 surface，并请求 frame callback 和 presentation feedback
+    fn present(
+        output_surface: &mut OutputSurface,
+        outputs: &[OutputInfo],
+        presentation: Option<&wp_presentation::WpPresentation>,
+        qh: &QueueHandle<Self>,
+        buffer: &wl_buffer::WlBuffer,
+        layout: &layout::Layout,
+        damage: &[DamageRect],
+    ) {
+        let (buffer_width, buffer_height) = layout.canvas;
+        let surface = &output_surface.surface;
+        surface.attach(Some(buffer), 0, 0);
+
+        // 如果支持 viewporter，使用它来设置源和目标矩形
+        if let Some(ref viewport) = output_surface.viewport {
+            let (logical_width, logical_height) = output_surface.logical_size(outputs);
+            // 设置源矩形（fill 模式下为裁剪区域）
+            let (x, y, w, h) = layout.source;
+            viewport.set_source(x, y, w, h);
+            // 设置目标矩形（整个屏幕，逻辑坐标）
+            viewport.set_destination(logical_width as i32, logical_height as i32);
+        } else {
+            // 回退到传统的缩放方式：buffer 尺寸必须是整数缩放的倍数
+            let scale = output_surface.scale(outputs) as u32;
+            if scale > 1 && buffer_width % scale == 0 && buffer_height % scale == 0 {
+                surface.set_buffer_scale(scale as i32);
+            } else {
+                surface.set_buffer_scale(1);
+            }
+        }
+
+        // 使用 buffer 坐标，不受 viewport 和 buffer scale 影响
+        for rect in damage {
+            surface.damage_buffer(rect.x, rect.y, rect.width, rect.height);
+        }
+        output_surface.last_geometry = Some((layout.clone(), output_surface.logical_size(outputs)));
+
+        // 请求 frame callback，compositor 准备好接收下一帧时回调
+        surface.frame(qh, (output_surface.output_id, output_surface.generation));
+        output_surface.frame_pending = true;
+        if let Some(presentation) = presentation {
+            presentation.feedback(surface, qh, output_surface.output_id);
+        }
+
+        surface.commit();
+    }
+
+    /// 标记连接已断开；如果是 DMA-BUF 导入引起的协议错误，重连后不再使用 DMA-BUF
+    fn mark_connection_lost(&mut self) {
+        self.connection_lost = true;
+        if let Some(error) = self.conn.protocol_error() {
+            log::error!(
+                "Wayland protocol error on {}: {} (code {})",
+                error.object_interface, error.message, error.code
+            );
+            if error.object_interface.starts_with("zwp_linux_buffer_params")
+                || (!self.dmabuf_buffers.is_empty() && error.object_interface == "wl_surface")
+            {
+                self.dmabuf_failed = true;
+            }
+        }
+    }
+
+    /// 发送缓冲区中的请求
+    fn flush(&mut self) -> Result<()> {
+        match self.conn.flush() {
+            Ok(()) => Ok(()),
+            Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(()),
+            Err(e) => {
+                self.mark_connection_lost();
+                Err(anyhow::anyhow!("Failed to flush Wayland connection: {}", e))
+            }
+        }
+    }
+
+    /// 非阻塞地读取并处理 socket 上已有的事件
+    pub fn dispatch_events(&mut self) -> Result<()> {
+        self.flush()?;
+        if self.queue.is_some() {
+            let mut queue = self.queue.take().unwrap();
+            let qh = queue.handle();
+            let result = Self::read_and_dispatch(&mut queue, self);
+            self.queue = Some(queue);
+            if let Err(e) = result {
+                // 后端错误（socket 断开、协议错误）之后连接不可恢复
+                if matches!(e, DispatchError::Backend(_)) {
+                    self.mark_connection_lost();
+                }
+                return Err(anyhow::anyhow!("Failed to dispatch events: {}", e));
+            }
+            self.sync_surfaces(&qh);
         }
+        Ok(())
+    }
 
-        // 使用 mmap 直接写入内存，避免系统调用
-        let write_start = std::time::Instant::now();
-        if let Some(shm_data) = self.shm_data {
-            unsafe {
-                let dst_ptr = shm_data as *mut u8;
-                let src_ptr = frame_data.as_ptr();
-                // 使用 memcpy 直接拷贝到 mmap 区域
-                std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, size as usize);
+    fn read_and_dispatch(queue: &mut wayland_client::EventQueue<Self>, state: &mut Self) -> Result<(), DispatchError> {
+        loop {
+            queue.dispatch_pending(state)?;
+            let Some(guard) = queue.prepare_read() else {
+                continue;
+            };
+            match guard.read() {
+                Ok(_) => {}
+                // socket 上没有更多数据
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(DispatchError::Backend(e)),
             }
         }
-        let write_time = write_start.elapsed();
-
-        // 使用三缓冲：获取当前 buffer，如果不存在则创建
-        let buffer_start = std::time::Instant::now();
-        let buffer = if let Some(ref buf) = self.buffers[self.current_buffer_index] {
-            buf.clone()
-        } else {
-            // 创建新的 buffer
-            let new_buffer = shm_pool.create_buffer(
-                0,
-                width as i32,
-                height as i32,
-                stride as i32,
-                wl_shm::Format::Argb8888,
-                &qh,
-                (),
-            );
-            self.buffers[self.current_buffer_index] = Some(new_buffer.clone());
-            new_buffer
-        };
-        let buffer_time = buffer_start.elapsed();
-
-        // 切换到下一个 buffer
-        self.current_buffer_index = (self.current_buffer_index + 1) % self.buffers.len();
+        queue.dispatch_pending(state)?;
+        Ok(())
+    }
 
-        // Debug: log first few pixels (BGRA format) every 30 frames
-        self.frame_count += 1;
-        if self.frame_count % 30 == 0 {
-            log::info!("Frame {} - {}x{} - First 2 pixels (BGRA): B={}, G={}, R={}, A={}, B={}, G={}, R={}, A={}",
-                     self.frame_count, width, height,
-                     frame_data[0], frame_data[1], frame_data[2], frame_data[3],
-                     frame_data[4], frame_data[5], frame_data[6], frame_data[7]);
+    /// 等待 socket 可读或到达 deadline
+    async fn wait_readable(&mut self, deadline: Instant) -> Result<()> {
+        if self.poll_fd.is_none() {
+            let fd = self.conn.backend().poll_fd().as_raw_fd();
+            self.poll_fd = Some(AsyncFd::with_interest(fd, Interest::READABLE)?);
         }
+        let poll_fd = self.poll_fd.as_ref().unwrap();
+        let deadline = tokio::time::Instant::from_std(deadline);
+        if let Ok(guard) = tokio::time::timeout_at(deadline, poll_fd.readable()).await {
+            // 之后的 dispatch_events 会一直读到 WouldBlock，这里可以直接清除就绪状态
+            guard?.clear_ready();
+        }
+        Ok(())
+    }
 
-        // Attach and commit
-        let commit_start = std::time::Instant::now();
-        surface.attach(Some(&buffer), 0, 0);
-        
-        // 如果支持 viewporter，使用它来设置源和目标矩形
-        if let Some(ref viewport) = self.viewport {
-            // 设置源矩形（整个视频帧）
-            viewport.set_source(0.0, 0.0, width as f64, height as f64);
-            // 设置目标矩形（整个屏幕）
-            viewport.set_destination(self.output_width as i32, self.output_height as i32);
-        } else {
-            // 回退到传统的缩放方式
-            surface.set_buffer_scale(1);
+    /// 是否有 surface 可以立即提交新帧（frame callback 已返回）
+    pub fn is_ready(&self) -> bool {
+        self.surfaces.iter().any(|s| s.configured && !s.frame_pending)
+    }
+
+    /// 等待直到至少一个 surface 可以提交新帧，或到达 deadline，返回是否就绪
+    pub async fn wait_ready(&mut self, deadline: Instant) -> Result<bool> {
+        loop {
+            self.dispatch_events()?;
+            if self.is_ready() {
+                return Ok(true);
+            }
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+            self.wait_readable(deadline).await?;
         }
-        
-        surface.damage(0, 0, width as i32, height as i32);
-        surface.commit();
-        let commit_time = commit_start.elapsed();
+    }
 
-        // Log timing every 30 frames
-        static COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
-        let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
-        if count % 30 == 0 {
-            log::info!("Render timing: mmap_write={:.2}ms, buffer_get={:.2}ms, commit={:.2}ms",
-                     write_time.as_secs_f64() * 1000.0,
-                     buffer_time.as_secs_f64() * 1000.0,
-                     commit_time.as_secs_f64() * 1000.0);
+    /// 等待到 deadline，期间持续处理 Wayland 事件
+    pub async fn wait_until(&mut self, deadline: Instant) -> Result<()> {
+        loop {
+            self.dispatch_events()?;
+            if Instant::now() >= deadline {
+                return Ok(());
+            }
+            self.wait_readable(deadline).await?;
         }
+    }
 
-        Ok(())
+    /// 显示刷新间隔：优先使用 wp_presentation 反馈，其次是 wl_output 的刷新率
+    pub fn refresh_interval(&self) -> Duration {
+        let presented = self.surfaces.iter().filter_map(|s| s.refresh_interval).min();
+        let from_mode = self
+            .surfaces
+            .iter()
+            .filter_map(|s| self.outputs.iter().find(|o| o.global_name == s.output_id))
+            .filter(|o| o.refresh > 0)
+            .map(|o| Duration::from_secs_f64(1000.0 / o.refresh as f64))
+            .min();
+        presented.or(from_mode).unwrap_or(Duration::from_micros(16_667))
     }
 
-    pub fn dispatch_events(&mut self) -> Result<()> {
-        // 每帧都 dispatch，但使用 roundtrip 保持流畅
-        if self.queue.is_some() {
-            let mut queue = self.queue.take().unwrap();
-            let result = queue.roundtrip(self);
-            self.queue = Some(queue);
-            result.map_err(|e| anyhow::anyhow!("Failed to dispatch events: {}", e))?;
+    /// compositor 可以导入的 DMA-BUF 格式；导入失败或不支持时为空
+    pub fn dmabuf_formats(&self) -> &[DmabufFormat] {
+        if self.dmabuf_failed || self.linux_dmabuf.is_none() {
+            return &[];
         }
-        Ok(())
+        &self.dmabuf_formats
+    }
+
+    /// compositor 是否拒绝过 DMA-BUF 导入
+    pub fn dmabuf_import_failed(&self) -> bool {
+        self.dmabuf_failed
     }
 
+    /// 零拷贝提交一帧 DMA-BUF，`frame` 会保持到 compositor release 该 buffer
+    /// 只支持不需要合成的缩放方式（fill/stretch）
     pub fn render_frame_dmabuf(
         &mut self,
-        fd: i32,
-        width: u32,
-        height: u32,
-        stride: u32,
-        format: u32,
-        modifier_hi: u32,
-        modifier_lo: u32,
+        attributes: &DmabufAttributes,
+        frame: Box<dyn std::any::Any + Send>,
     ) -> Result<()> {
-        if !self.configured {
-            return Ok(());
-        }
-
-        let surface = self.surface.as_ref().ok_or_else(|| anyhow::anyhow!("Surface not available"))?;
         let linux_dmabuf = self.linux_dmabuf.as_ref().ok_or_else(|| anyhow::anyhow!("DMA-BUF not available"))?;
+        if self.dmabuf_failed {
+            return Err(anyhow::anyhow!("DMA-BUF import failed before"));
+        }
+        let requested = DmabufFormat { format: attributes.format, modifier: attributes.modifier };
+        if !self.dmabuf_formats.contains(&requested) {
+            return Err(anyhow::anyhow!(
+                "DMA-BUF format 0x{:08x} with modifier 0x{:016x} not supported by compositor",
+                attributes.format, attributes.modifier
+            ));
+        }
 
         let queue = self.queue.as_mut().ok_or_else(|| anyhow::anyhow!("Queue not available"))?;
         let qh = queue.handle();
 
-        // 添加调试日志
-        log::info!("DMA-BUF params: fd={}, width={}, height={}, stride={}, format=0x{:08x}, modifier_hi=0x{:08x}, modifier_lo=0x{:08x}",
-                   fd, width, height, stride, format, modifier_hi, modifier_lo);
+        let (width, height) = (attributes.width, attributes.height);
+        let scaling = self.options.scaling;
+        let layouts: Vec<(usize, layout::Layout)> = self
+            .compute_layouts((width, height))
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| self.surfaces[*i].configured && !self.surfaces[*i].frame_pending)
+            .collect();
+        if layouts.is_empty() {
+            // 没有 surface 在等待新帧，直接丢弃
+            return Ok(());
+        }
+        if layouts.iter().any(|(_, layout)| !layout.is_direct((width, height))) {
+            return Err(anyhow::anyhow!("Scaling mode {} needs compositing, DMA-BUF not usable", scaling));
+        }
 
         let params = linux_dmabuf.create_params(&qh, ());
-        let borrowed_fd = unsafe { BorrowedFd::borrow_raw(fd) };
-        params.add(borrowed_fd, 0, 0, stride, modifier_hi, modifier_lo);
+        let modifier_hi = (attributes.modifier >> 32) as u32;
+        let modifier_lo = (attributes.modifier & 0xffff_ffff) as u32;
+        for (index, plane) in attributes.planes.iter().enumerate() {
+            let borrowed_fd = unsafe { BorrowedFd::borrow_raw(plane.fd) };
+            params.add(borrowed_fd, index as u32, plane.offset, plane.stride, modifier_hi, modifier_lo);
+        }
         use wayland_protocols::wp::linux_dmabuf::zv1::client::zwp_linux_buffer_params_v1::Flags;
-        let buffer = params.create_immed(width as i32, height as i32, format, Flags::empty(), &qh, ());
+        let id = self.next_dmabuf_id;
+        self.next_dmabuf_id += 1;
+        let buffer = params.create_immed(width as i32, height as i32, attributes.format, Flags::empty(), &qh, id);
+        params.destroy();
 
-        surface.attach(Some(&buffer), 0, 0);
-        surface.damage(0, 0, width as i32, height as i32);
-        surface.commit();
+        // YUV 格式没有 alpha 通道，只有设置了 opacity 时才不是不透明的
+        let opaque = !self.options.is_translucent();
+        let damage = [DamageRect::full(width, height)];
+        for (index, layout) in &layouts {
+            let output_surface = &mut self.surfaces[*index];
+            output_surface.update_opaque_region(self.compositor.as_ref(), &self.outputs, &qh, opaque);
+            Self::present(output_surface, &self.outputs, self.presentation.as_ref(), &qh, &buffer, layout, &damage);
+            output_surface.last_shm_buffer = None;
+        }
+        self.dmabuf_buffers.push((id, buffer, frame));
 
-        Ok(())
+        self.flush()
     }
 }
 
 impl Dispatch<zwp_linux_buffer_params_v1::ZwpLinuxBufferParamsV1, ()> for WaylandApp {
     fn event(
-        _state: &mut Self,
+        state: &mut Self,
         _proxy: &zwp_linux_buffer_params_v1::ZwpLinuxBufferParamsV1,
         event: zwp_linux_buffer_params_v1::Event,
         _data: &(),
@@ -338,12 +1371,12 @@ impl Dispatch<zwp_linux_buffer_params_v1::ZwpLinuxBufferParamsV1, ()> for Waylan
     ) {
         match event {
             zwp_linux_buffer_params_v1::Event::Created { buffer } => {
-                // The buffer is created, we can now use it.
-                // In this example, we don't need to do anything here,
-                // as we are creating the buffer and using it immediately.
+                // 只使用 create_immed，不会收到 created；以防万一直接销毁
+                buffer.destroy();
             }
             zwp_linux_buffer_params_v1::Event::Failed => {
-                log::error!("Failed to create DMA-BUF buffer");
+                log::error!("Compositor failed to import DMA-BUF, falling back to SHM");
+                state.dmabuf_failed = true;
             }
             _ => {}
         }
@@ -364,6 +1397,18 @@ impl Dispatch<wl_compositor::WlCompositor, ()> for WaylandApp {
     }
 }
 
+impl Dispatch<wl_region::WlRegion, ()> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_region::WlRegion,
+        _event: wl_region::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
 impl Dispatch<wl_surface::WlSurface, ()> for WaylandApp {
     fn event(
         _state: &mut Self,
@@ -378,13 +1423,16 @@ impl Dispatch<wl_surface::WlSurface, ()> for WaylandApp {
 
 impl Dispatch<wl_shm::WlShm, ()> for WaylandApp {
     fn event(
-        _state: &mut Self,
+        state: &mut Self,
         _proxy: &wl_shm::WlShm,
-        _event: wl_shm::Event,
+        event: wl_shm::Event,
         _data: &(),
         _conn: &Connection,
         _qhandle: &QueueHandle<Self>,
     ) {
+        if let wl_shm::Event::Format { format: wayland_client::WEnum::Value(format) } = event {
+            state.shm_formats.push(format);
+        }
     }
 }
 
@@ -413,35 +1461,215 @@ impl Dispatch<wl_buffer::WlBuffer, ()> for WaylandApp {
     }
 }
 
-impl Dispatch<wl_output::WlOutput, ()> for WaylandApp {
+impl Dispatch<wl_buffer::WlBuffer, (u32, usize)> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        proxy: &wl_buffer::WlBuffer,
+        event: wl_buffer::Event,
+        data: &(u32, usize),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            // compositor 不再读取该 buffer，可以重新写入
+            let (output_id, index) = *data;
+            let Some(output_surface) = state.surfaces.iter_mut().find(|s| s.output_id == output_id) else {
+                return;
+            };
+            if output_surface.release_retired(proxy) {
+                return;
+            }
+            if let Some(shm_buffer) = output_surface
+                .buffers
+                .get_mut(index)
+                .filter(|b| b.buffer.as_ref() == Some(proxy))
+            {
+                shm_buffer.busy = false;
+            }
+        }
+    }
+}
+
+/// DMA-BUF buffer，user data 为 dmabuf_buffers 中的 id
+impl Dispatch<wl_buffer::WlBuffer, u64> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        proxy: &wl_buffer::WlBuffer,
+        event: wl_buffer::Event,
+        id: &u64,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            // 释放帧，解码器可以复用对应的硬件 surface
+            state.dmabuf_buffers.retain(|(buffer_id, _, _)| buffer_id != id);
+            proxy.destroy();
+        }
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, u32> for WaylandApp {
     fn event(
         state: &mut Self,
         _proxy: &wl_output::WlOutput,
         event: wl_output::Event,
-        _data: &(),
+        global_name: &u32,
         _conn: &Connection,
         _qhandle: &QueueHandle<Self>,
     ) {
+        let Some(output) = state.outputs.iter_mut().find(|o| o.global_name == *global_name) else {
+            return;
+        };
         match event {
             wl_output::Event::Mode {
                 flags,
                 width,
                 height,
                 refresh,
-                ..
             } => {
                 // Only consider current mode (not preferred)
-                if flags == wayland_client::WEnum::Value(wl_output::Mode::Current) {
-                    state.output_width = width as u32;
-                    state.output_height = height as u32;
-                    log::info!("Output size: {}x{}, refresh: {}mHz", width, height, refresh);
+                if let wayland_client::WEnum::Value(flags) = flags {
+                    if flags.contains(wl_output::Mode::Current) {
+                        output.width = width as u32;
+                        output.height = height as u32;
+                        output.refresh = refresh;
+                        log::info!("Output {} size: {}x{}, refresh: {}mHz", output.display_name(), width, height, refresh);
+                    }
                 }
             }
             wl_output::Event::Scale {
                 factor,
+            } => {
+                output.scale = factor;
+                log::info!("Output {} scale factor: {}", output.display_name(), factor);
+            }
+            wl_output::Event::Geometry {
+                x,
+                y,
+                physical_width,
+                physical_height,
+                transform,
                 ..
             } => {
-                log::info!("Output scale factor: {}", factor);
+                if let wayland_client::WEnum::Value(transform) = transform {
+                    output.transform = transform;
+                }
+                output.position = (x, y);
+                output.physical_size_mm = (physical_width, physical_height);
+            }
+            wl_output::Event::Name { name } => {
+                output.name = Some(name);
+            }
+            wl_output::Event::Description { description } => {
+                output.description = Some(description);
+            }
+            wl_output::Event::Done => {
+                output.done = true;
+                // 输出状态有更新，之前被关闭的 surface 可以重新创建
+                output.surface_closed = false;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<zxdg_output_manager_v1::ZxdgOutputManagerV1, ()> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &zxdg_output_manager_v1::ZxdgOutputManagerV1,
+        _event: zxdg_output_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<zxdg_output_v1::ZxdgOutputV1, u32> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        _proxy: &zxdg_output_v1::ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        global_name: &u32,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        let Some(output) = state.outputs.iter_mut().find(|o| o.global_name == *global_name) else {
+            return;
+        };
+        match event {
+            zxdg_output_v1::Event::LogicalPosition { x, y } => {
+                output.logical_position = Some((x, y));
+            }
+            zxdg_output_v1::Event::LogicalSize { width, height } => {
+                output.logical_size = Some((width.max(0) as u32, height.max(0) as u32));
+                log::info!("Output {} logical size: {}x{}", output.display_name(), width, height);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// frame callback，user data 为输出和 surface 的 generation
+impl Dispatch<wl_callback::WlCallback, (u32, u64)> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        _proxy: &wl_callback::WlCallback,
+        event: wl_callback::Event,
+        data: &(u32, u64),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            // frame callback：compositor 准备好接收下一帧；已销毁的旧 surface 的回调不影响新的 surface
+            let (output_id, generation) = *data;
+            if let Some(output_surface) =
+                state.surfaces.iter_mut().find(|s| s.output_id == output_id && s.generation == generation)
+            {
+                output_surface.frame_pending = false;
+            }
+        }
+    }
+}
+
+impl Dispatch<wp_presentation::WpPresentation, ()> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wp_presentation::WpPresentation,
+        _event: wp_presentation::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wp_presentation_feedback::WpPresentationFeedback, u32> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        _proxy: &wp_presentation_feedback::WpPresentationFeedback,
+        event: wp_presentation_feedback::Event,
+        output_id: &u32,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        let Some(output_surface) = state.surfaces.iter_mut().find(|s| s.output_id == *output_id) else {
+            return;
+        };
+        match event {
+            wp_presentation_feedback::Event::Presented { refresh, .. } => {
+                output_surface.presented_frames += 1;
+                if refresh > 0 {
+                    output_surface.refresh_interval = Some(Duration::from_nanos(refresh as u64));
+                }
+                if output_surface.presented_frames % 300 == 0 {
+                    log::info!("Output {}: presented {} frames, discarded {}, refresh {:?}",
+                               output_id, output_surface.presented_frames,
+                               output_surface.discarded_frames, output_surface.refresh_interval);
+                }
+            }
+            wp_presentation_feedback::Event::Discarded => {
+                output_surface.discarded_frames += 1;
             }
             _ => {}
         }
@@ -496,12 +1724,66 @@ impl Dispatch<wp_viewport::WpViewport, ()> for WaylandApp {
     }
 }
 
-impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for WaylandApp {
+impl Dispatch<wp_alpha_modifier_v1::WpAlphaModifierV1, ()> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wp_alpha_modifier_v1::WpAlphaModifierV1,
+        _event: wp_alpha_modifier_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wp_alpha_modifier_surface_v1::WpAlphaModifierSurfaceV1, ()> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wp_alpha_modifier_surface_v1::WpAlphaModifierSurfaceV1,
+        _event: wp_alpha_modifier_surface_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1, ()> for WaylandApp {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+        _event: wp_fractional_scale_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wp_fractional_scale_v1::WpFractionalScaleV1, u32> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        _proxy: &wp_fractional_scale_v1::WpFractionalScaleV1,
+        event: wp_fractional_scale_v1::Event,
+        output_id: &u32,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let wp_fractional_scale_v1::Event::PreferredScale { scale } = event {
+            if let Some(output_surface) = state.surfaces.iter_mut().find(|s| s.output_id == *output_id) {
+                log::info!("Output {} preferred scale: {:.3}", output_id, scale as f64 / 120.0);
+                output_surface.preferred_scale = Some(scale);
+            }
+        }
+    }
+}
+
+impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, u32> for WaylandApp {
     fn event(
         state: &mut Self,
         _proxy: &zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
         event: zwlr_layer_surface_v1::Event,
-        _data: &(),
+        output_id: &u32,
         _conn: &Connection,
         _qhandle: &QueueHandle<Self>,
     ) {
@@ -512,12 +1794,28 @@ impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for WaylandApp {
                 height,
             } => {
                 _proxy.ack_configure(serial);
-                state.configured = true;
-                state.configured_width = width;
-                state.configured_height = height;
+                if let Some(output_surface) = state.surfaces.iter_mut().find(|s| s.output_id == *output_id) {
+                    // 运行时的重新 configure（旋转、缩放变化等）在下一帧的 viewport 上生效
+                    if output_surface.configured
+                        && (output_surface.configured_width != width || output_surface.configured_height != height)
+                    {
+                        log::info!(
+                            "Layer surface on output {} reconfigured: {}x{} -> {}x{}",
+                            output_id, output_surface.configured_width, output_surface.configured_height, width, height
+                        );
+                    }
+                    output_surface.configured = true;
+                    output_surface.configured_width = width;
+                    output_surface.configured_height = height;
+                }
             }
             zwlr_layer_surface_v1::Event::Closed => {
-                std::process::exit(0);
+                // 不在这里退出进程，交给渲染任务决定重建 surface 或等待输出恢复
+                log::warn!("Layer surface on output {} closed by compositor", output_id);
+                if let Some(output_surface) = state.surfaces.iter_mut().find(|s| s.output_id == *output_id) {
+                    output_surface.configured = false;
+                }
+                state.events.push(WaylandEvent::SurfaceClosed { output_id: *output_id });
             }
             _ => {}
         }
@@ -533,15 +1831,84 @@ impl Dispatch<zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1, ()> for WaylandApp {
         _conn: &Connection,
         _qhandle: &QueueHandle<Self>,
     ) {
+        // v3 及以下版本通过 format/modifier 事件报告支持的格式
         match event {
             zwp_linux_dmabuf_v1::Event::Format { format } => {
-                state.dmabuf_formats.push(format);
+                state.dmabuf_formats.push(DmabufFormat { format, modifier: DRM_FORMAT_MOD_INVALID });
+            }
+            zwp_linux_dmabuf_v1::Event::Modifier { format, modifier_hi, modifier_lo } => {
+                let modifier = ((modifier_hi as u64) << 32) | modifier_lo as u64;
+                state.dmabuf_formats.push(DmabufFormat { format, modifier });
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1, ()> for WaylandApp {
+    fn event(
+        state: &mut Self,
+        _proxy: &zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1,
+        event: zwp_linux_dmabuf_feedback_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_linux_dmabuf_feedback_v1::Event::FormatTable { fd, size } => {
+                match read_format_table(fd.as_fd(), size as usize) {
+                    Ok(table) => state.dmabuf_format_table = table,
+                    Err(e) => log::error!("Failed to read DMA-BUF format table: {}", e),
+                }
+            }
+            zwp_linux_dmabuf_feedback_v1::Event::MainDevice { device } => {
+                state.dmabuf_main_device = device.try_into().ok().map(u64::from_ne_bytes);
+            }
+            zwp_linux_dmabuf_feedback_v1::Event::TrancheFormats { indices } => {
+                // 索引为 u16 数组，指向格式表
+                for index in indices.chunks_exact(2) {
+                    let index = u16::from_ne_bytes([index[0], index[1]]) as usize;
+                    if let Some(format) = state.dmabuf_format_table.get(index) {
+                        if !state.dmabuf_pending_formats.contains(format) {
+                            state.dmabuf_pending_formats.push(*format);
+                        }
+                    }
+                }
+            }
+            zwp_linux_dmabuf_feedback_v1::Event::Done => {
+                state.dmabuf_formats = std::mem::take(&mut state.dmabuf_pending_formats);
+                log::debug!("DMA-BUF feedback: {} format/modifier pairs", state.dmabuf_formats.len());
             }
             _ => {}
         }
     }
 }
 
+/// 读取 linux-dmabuf v4 的格式表（每项 16 字节：u32 format、u32 padding、u64 modifier）
+fn read_format_table(fd: BorrowedFd, size: usize) -> Result<Vec<DmabufFormat>> {
+    if size == 0 {
+        return Ok(Vec::new());
+    }
+    let data = unsafe {
+        libc::mmap(std::ptr::null_mut(), size, libc::PROT_READ, libc::MAP_PRIVATE, fd.as_raw_fd(), 0)
+    };
+    if data == libc::MAP_FAILED {
+        return Err(anyhow::anyhow!("Failed to mmap format table"));
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(data as *const u8, size) };
+    let table = bytes
+        .chunks_exact(16)
+        .map(|entry| DmabufFormat {
+            format: u32::from_ne_bytes(entry[0..4].try_into().unwrap()),
+            modifier: u64::from_ne_bytes(entry[8..16].try_into().unwrap()),
+        })
+        .collect();
+    unsafe {
+        libc::munmap(data, size);
+    }
+    Ok(table)
+}
+
 impl Dispatch<wl_registry::WlRegistry, ()> for WaylandApp {
     fn event(
         state: &mut Self,
@@ -555,7 +1922,7 @@ impl Dispatch<wl_registry::WlRegistry, ()> for WaylandApp {
             wl_registry::Event::Global {
                 name,
                 interface,
-                version: _,
+                version,
             } => {
                 log::info!("Global: {} (name: {})", interface, name);
                 match interface.as_str() {
@@ -585,8 +1952,14 @@ impl Dispatch<wl_registry::WlRegistry, ()> for WaylandApp {
                     }
                     "wl_output" => {
                         // Bind output to get display size information
-                        let _output = registry.bind::<wl_output::WlOutput, _, _>(name, 4, qhandle, ());
-                        log::info!("Bound wl_output");
+                        // name/description 事件需要 version 4
+                        let output = registry.bind::<wl_output::WlOutput, _, _>(name, version.min(4), qhandle, name);
+                        let mut info = OutputInfo::new(name, output);
+                        if let Some(ref manager) = state.xdg_output_manager {
+                            info.xdg_output = Some(manager.get_xdg_output(&info.output, qhandle, name));
+                        }
+                        state.outputs.push(info);
+                        log::info!("Bound wl_output (name: {})", name);
                     }
                     "wp_viewporter" => {
                         state.viewporter = Some(registry.bind::<wp_viewporter::WpViewporter, _, _>(
@@ -597,10 +1970,53 @@ impl Dispatch<wl_registry::WlRegistry, ()> for WaylandApp {
                         ));
                         log::info!("Bound wp_viewporter");
                     }
+                    "wp_presentation" => {
+                        state.presentation = Some(registry.bind::<wp_presentation::WpPresentation, _, _>(
+                            name,
+                            1,
+                            qhandle,
+                            (),
+                        ));
+                        log::info!("Bound wp_presentation");
+                    }
+                    "wp_fractional_scale_manager_v1" => {
+                        state.fractional_scale_manager = Some(
+                            registry.bind::<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1, _, _>(
+                                name,
+                                1,
+                                qhandle,
+                                (),
+                            ),
+                        );
+                        log::info!("Bound wp_fractional_scale_manager_v1");
+                    }
+                    "zxdg_output_manager_v1" => {
+                        let manager = registry.bind::<zxdg_output_manager_v1::ZxdgOutputManagerV1, _, _>(
+                            name,
+                            version.min(3),
+                            qhandle,
+                            (),
+                        );
+                        // 在 manager 之前公布的输出
+                        for output in state.outputs.iter_mut().filter(|o| o.xdg_output.is_none()) {
+                            output.xdg_output = Some(manager.get_xdg_output(&output.output, qhandle, output.global_name));
+                        }
+                        state.xdg_output_manager = Some(manager);
+                        log::info!("Bound zxdg_output_manager_v1");
+                    }
+                    "wp_alpha_modifier_v1" => {
+                        state.alpha_modifier = Some(registry.bind::<wp_alpha_modifier_v1::WpAlphaModifierV1, _, _>(
+                            name,
+                            1,
+                            qhandle,
+                            (),
+                        ));
+                        log::info!("Bound wp_alpha_modifier_v1");
+                    }
                     "zwp_linux_dmabuf_v1" => {
                         state.linux_dmabuf = Some(registry.bind::<zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1, _, _>(
                             name,
-                            4,
+                            version.min(4),
                             qhandle,
                             (),
                         ));
@@ -609,7 +2025,10 @@ impl Dispatch<wl_registry::WlRegistry, ()> for WaylandApp {
                     _ => {}
                 }
             }
-            wl_registry::Event::GlobalRemove { name: _ } => {}
+            wl_registry::Event::GlobalRemove { name } => {
+                // 目前只有 wl_output 会在运行时被移除
+                state.remove_output(name);
+            }
             _ => {}
         }
     }